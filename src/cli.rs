@@ -1,5 +1,11 @@
 use crate::campus::Campus;
 use clap::{Parser, ValueEnum};
+use moodle_course_builder::{
+    BarProgress, DedupeStrategy, Dialect, FileFormat, JsonProgress, ProgressSink, SheetOptions,
+    SilentProgress,
+};
+use std::error::Error;
+use std::sync::Arc;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 pub enum CommandMode {
@@ -8,6 +14,86 @@ pub enum CommandMode {
     UserEnrol,
     FacultySeed,
     FacultyEnrol,
+    EnrolLonger,
+    EnrolWider,
+    RunSpec,
+    Diff,
+}
+
+impl CommandMode {
+    /// Name of the built-in spec this mode runs; see `spec::BUILTIN_SPECS`.
+    pub fn spec_name(self) -> &'static str {
+        match self {
+            CommandMode::Course => "course",
+            CommandMode::UserSeed => "user-seed",
+            CommandMode::UserEnrol => "user-enrol",
+            CommandMode::FacultySeed => "faculty-seed",
+            CommandMode::FacultyEnrol => "faculty-enrol",
+            CommandMode::EnrolLonger => "enrol-longer",
+            CommandMode::EnrolWider => "enrol-wider",
+            CommandMode::RunSpec => "run-spec",
+            CommandMode::Diff => "diff",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum DedupeMode {
+    KeepFirst,
+    KeepLast,
+    Error,
+}
+
+impl From<DedupeMode> for DedupeStrategy {
+    fn from(mode: DedupeMode) -> Self {
+        match mode {
+            DedupeMode::KeepFirst => DedupeStrategy::KeepFirst,
+            DedupeMode::KeepLast => DedupeStrategy::KeepLast,
+            DedupeMode::Error => DedupeStrategy::Error,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum QuoteMode {
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+impl From<QuoteMode> for csv::QuoteStyle {
+    fn from(mode: QuoteMode) -> Self {
+        match mode {
+            QuoteMode::Necessary => csv::QuoteStyle::Necessary,
+            QuoteMode::Always => csv::QuoteStyle::Always,
+            QuoteMode::NonNumeric => csv::QuoteStyle::NonNumeric,
+            QuoteMode::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum OutFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl From<OutFormat> for FileFormat {
+    fn from(format: OutFormat) -> Self {
+        match format {
+            OutFormat::Csv => FileFormat::Csv,
+            OutFormat::Json => FileFormat::Json,
+            OutFormat::Ndjson => FileFormat::Ndjson,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum ProgressMode {
+    Bars,
+    Json,
 }
 
 #[derive(Parser)]
@@ -17,13 +103,29 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = CommandMode::Course)]
     pub mode: CommandMode,
 
+    /// Pipeline spec (.toml, .yaml or .yml) to run, or the name of a built-in spec
+    #[arg(long, required_if_eq("mode", "run-spec"))]
+    pub spec: Option<String>,
+
+    /// Set a spec variable, referred to as ${NAME} in the spec
+    #[arg(long = "set", value_name = "NAME=VALUE", num_args = 1..)]
+    pub set: Vec<String>,
+
     // Courses where the users will be enrolled
     #[arg(long, num_args = 1.., required_if_eq("mode", "user-enrol"))]
     pub courses: Vec<String>,
 
-    /// Path to the input CSV file
-    #[arg(long, required_if_eq("mode", "course"))]
-    pub in_path: Option<String>,
+    /// Input files or glob patterns; several are concatenated and must share their columns
+    #[arg(long, num_args = 1.., required_if_eq_any([("mode", "course"), ("mode", "enrol-longer"), ("mode", "enrol-wider"), ("mode", "diff")]))]
+    pub in_path: Vec<String>,
+
+    /// Columns identifying a row when comparing the two --in-path files in diff mode
+    #[arg(long, num_args = 1.., required_if_eq("mode", "diff"))]
+    pub keys: Vec<String>,
+
+    /// Add a column with this name holding the input file of each row
+    #[arg(long)]
+    pub source_column: Option<String>,
 
     /// Path to the output CSV file
     #[arg(long)]
@@ -49,6 +151,105 @@ pub struct Cli {
     #[arg(long, default_value = "1")]
     pub count: usize,
 
+    /// Abort on the first row that fails a transformation instead of reporting all failures
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Write rows dropped for blank output values to this CSV, with a reason column
+    #[arg(long)]
+    pub rejects_path: Option<String>,
+
+    /// Fail instead of dropping rows with blank output values
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Remove users with duplicate usernames (user and faculty modes)
+    #[arg(long, value_enum)]
+    pub dedupe: Option<DedupeMode>,
+
+    /// Sort courses by program, year, semester and code, and users by username
+    #[arg(long)]
+    pub sort: bool,
+
+    /// Keep input columns that are not part of the output (enrol modes)
+    #[arg(long)]
+    pub passthrough: bool,
+
+    /// Read and write rows as typed structs, so a missing or malformed column fails the row
+    /// when it is read (course, seed and enrol modes)
+    #[arg(long)]
+    pub typed: bool,
+
+    /// Sheet to read when the input is an .xlsx/.ods workbook (defaults to the first sheet)
+    #[arg(long)]
+    pub sheet: Option<String>,
+
+    /// 1-based row holding the headers when the input is a workbook (defaults to the first row
+    /// that is not empty)
+    #[arg(long)]
+    pub header_row: Option<u32>,
+
+    /// Field delimiter of the input CSV
+    #[arg(long, default_value_t = ',')]
+    pub in_delimiter: char,
+
+    /// Field delimiter of the output CSV
+    #[arg(long, default_value_t = ',')]
+    pub out_delimiter: char,
+
+    /// Quote character for input and output
+    #[arg(long, default_value_t = '"')]
+    pub quote: char,
+
+    /// When to quote output fields
+    #[arg(long, value_enum, default_value_t = QuoteMode::Necessary)]
+    pub quote_style: QuoteMode,
+
+    /// Format of the output file; picked from the --out-path extension by default
+    #[arg(long, value_enum)]
+    pub out_format: Option<OutFormat>,
+
+    /// Encoding of the input CSV (e.g. utf-8, windows-1252)
+    #[arg(long, default_value = "utf-8")]
+    pub encoding: String,
+
+    /// Keep a byte order mark at the start of the input instead of stripping it
+    #[arg(long)]
+    pub keep_bom: bool,
+
+    /// Start the output with a UTF-8 byte order mark
+    #[arg(long)]
+    pub write_bom: bool,
+
+    /// Trim whitespace around input headers and values
+    #[arg(long)]
+    pub trim: bool,
+
+    /// How pipeline steps report progress on stderr
+    #[arg(long, value_enum, default_value_t = ProgressMode::Bars)]
+    pub progress: ProgressMode,
+
+    /// Do not report progress at all
+    #[arg(long, conflicts_with = "progress")]
+    pub quiet: bool,
+
+    /// Write one file per distinct value of these columns; --out-path is then a template
+    /// such as 'out/{Program|lower}/{Year}.csv'
+    #[arg(long, num_args = 1..)]
+    pub split_by: Vec<String>,
+
+    /// Add a column from a template, e.g. 'idnumber={campus}-{Course Code|nospace|upper}'
+    #[arg(long, value_name = "NAME=TEMPLATE", num_args = 1..)]
+    pub derive: Vec<String>,
+
+    /// Write the steps of the run (rows in/out, columns added/dropped, time) to this JSON file
+    #[arg(long)]
+    pub lineage_path: Option<String>,
+
+    /// Evaluate derive and filter steps on all CPU cores
+    #[arg(long)]
+    pub parallel: bool,
+
     /// Run transformations without writing output
     #[arg(long)]
     pub dry_run: bool,
@@ -56,4 +257,94 @@ pub struct Cli {
     /// Show the first N output records in dry run mode
     #[arg(long, requires = "dry_run")]
     pub dry_run_show: Option<usize>,
+
+    /// Write N random output records to --sample-path for review (N per group with --sample-by)
+    #[arg(long, requires = "sample_path")]
+    pub sample: Option<usize>,
+
+    /// Draw the --sample from every combination of these columns, e.g. Program Year
+    #[arg(long, num_args = 1.., requires = "sample")]
+    pub sample_by: Vec<String>,
+
+    /// Seed for --sample, so reruns pick the same records
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Review CSV receiving the --sample records; written in dry runs too
+    #[arg(long, requires = "sample")]
+    pub sample_path: Option<String>,
+}
+
+fn ascii_byte(value: char, arg: &str) -> Result<u8, Box<dyn Error>> {
+    if !value.is_ascii() {
+        return Err(format!("--{} must be a single ASCII character", arg).into());
+    }
+    Ok(value as u8)
+}
+
+impl Cli {
+    /// Every `--derive` split into column name and template.
+    pub fn derives(&self) -> Result<Vec<(&str, &str)>, Box<dyn Error>> {
+        self.derive
+            .iter()
+            .map(|derive| {
+                derive
+                    .split_once('=')
+                    .map(|(name, template)| (name.trim(), template))
+                    .filter(|(name, _)| !name.is_empty())
+                    .ok_or_else(|| {
+                        format!("--derive expects NAME=TEMPLATE, got '{}'", derive).into()
+                    })
+            })
+            .collect()
+    }
+
+    /// Sheet and header row used when `--in-path` is a workbook.
+    pub fn sheet_options(&self) -> SheetOptions {
+        SheetOptions {
+            sheet: self.sheet.clone(),
+            header_row: self.header_row,
+        }
+    }
+
+    /// Dialect used to read `--in-path`.
+    pub fn input_dialect(&self) -> Result<Dialect, Box<dyn Error>> {
+        Ok(Dialect {
+            delimiter: ascii_byte(self.in_delimiter, "in-delimiter")?,
+            quote: ascii_byte(self.quote, "quote")?,
+            encoding: Dialect::encoding_for_label(&self.encoding)?,
+            strip_bom: !self.keep_bom,
+            trim: self.trim,
+            ..Dialect::default()
+        })
+    }
+
+    /// Dialect used to write `--out-path` and `--rejects-path`.
+    pub fn output_dialect(&self) -> Result<Dialect, Box<dyn Error>> {
+        Ok(Dialect {
+            delimiter: ascii_byte(self.out_delimiter, "out-delimiter")?,
+            quote: ascii_byte(self.quote, "quote")?,
+            quote_style: self.quote_style.into(),
+            write_bom: self.write_bom,
+            ..Dialect::default()
+        })
+    }
+
+    /// Format of `--out-path`: `--out-format` if given, otherwise from the extension.
+    pub fn output_format(&self) -> FileFormat {
+        self.out_format
+            .map(FileFormat::from)
+            .unwrap_or_else(|| FileFormat::from_path(&self.out_path))
+    }
+
+    /// Progress sink picked by `--quiet` and `--progress`.
+    pub fn progress_sink(&self) -> Arc<dyn ProgressSink> {
+        if self.quiet {
+            return Arc::new(SilentProgress);
+        }
+        match self.progress {
+            ProgressMode::Bars => Arc::new(BarProgress),
+            ProgressMode::Json => Arc::new(JsonProgress),
+        }
+    }
 }
@@ -0,0 +1,207 @@
+use crate::{Pipeline, Record};
+use chrono::NaiveDate;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Date layouts [`CastType::Date`] accepts, tried in order.
+const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%d-%b-%Y", "%B %d, %Y"];
+
+/// Type a column is checked and normalized to by [`Pipeline::cast`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CastType {
+    /// Whole number; a spreadsheet's `3.0` becomes `3`.
+    Integer,
+    /// Any finite number, written without trailing zeros.
+    Decimal,
+    /// A date written as `YYYY-MM-DD`, read from ISO, `MM/DD/YYYY` or spelled-out layouts.
+    Date,
+    /// `true`/`false`, also read from `yes`/`no`, `y`/`n` and `1`/`0`.
+    Boolean,
+}
+
+impl CastType {
+    /// Normalizes `value`, or explains why it is not of this type. Blank values stay blank.
+    pub fn apply(self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+
+        let cast = match self {
+            CastType::Integer => value
+                .parse::<i64>()
+                .ok()
+                .or_else(|| {
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|number| number.fract() == 0.0 && number.abs() < 1e15)
+                        .map(|number| number as i64)
+                })
+                .map(|number| number.to_string()),
+            CastType::Decimal => value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .map(|number| number.to_string()),
+            CastType::Date => DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .map(|date| date.format("%Y-%m-%d").to_string()),
+            CastType::Boolean => match value.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" => Some("true".to_string()),
+                "false" | "no" | "n" | "0" => Some("false".to_string()),
+                _ => None,
+            },
+        };
+        cast.ok_or_else(|| format!("cannot cast '{}' to {}", value, self))
+    }
+}
+
+impl fmt::Display for CastType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CastType::Integer => "integer",
+            CastType::Decimal => "decimal",
+            CastType::Date => "date",
+            CastType::Boolean => "boolean",
+        })
+    }
+}
+
+impl FromStr for CastType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "integer" | "int" => Ok(CastType::Integer),
+            "decimal" | "number" => Ok(CastType::Decimal),
+            "date" => Ok(CastType::Date),
+            "boolean" | "bool" => Ok(CastType::Boolean),
+            _ => Err(format!(
+                "unknown cast type '{}' (expected integer, decimal, date or boolean)",
+                name
+            )),
+        }
+    }
+}
+
+impl Pipeline {
+    /// Renames columns given as `(from, to)` pairs, keeping their position in every row.
+    ///
+    /// Fails if a source column does not exist or a new name is already taken by a column
+    /// that is not renamed itself.
+    pub fn rename(mut self, renames: Vec<(&str, &str)>) -> Result<Self, Box<dyn Error>> {
+        for &(from, to) in &renames {
+            if !self.headers.iter().any(|header| header == from) {
+                return Err(format!("cannot rename unknown column '{}'", from).into());
+            }
+            let taken = self.headers.iter().any(|header| header == to)
+                && !renames.iter().any(|&(other, _)| other == to);
+            if taken || renames.iter().filter(|&&(_, other)| other == to).count() > 1 {
+                return Err(format!("cannot rename '{}' to existing column '{}'", from, to).into());
+            }
+        }
+
+        let step = self.begin_step(format!(
+            "rename {}",
+            renames
+                .iter()
+                .map(|(from, to)| format!("{} -> {}", from, to))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        let new_name = |header: &str| -> String {
+            renames
+                .iter()
+                .find(|&&(from, _)| from == header)
+                .map_or(header, |&(_, to)| to)
+                .to_string()
+        };
+
+        let pb = self
+            .progress
+            .start("Renaming columns...", Some(self.records.len() as u64));
+        for record in &mut self.records {
+            let mut renamed = Record::with_capacity(record.len());
+            renamed.set_position(record.position().cloned());
+            for (header, value) in std::mem::take(record) {
+                renamed.insert(new_name(&header), value);
+            }
+            *record = renamed;
+            pb.inc(1);
+        }
+        self.headers = self.headers.iter().map(|h| new_name(h)).collect();
+        self.passthrough = self.passthrough.iter().map(|h| new_name(h)).collect();
+        pb.finish("Finished renaming columns");
+        self.end_step(step);
+        Ok(self)
+    }
+
+    /// Moves `headers` to the front in the given order; the other columns follow in their
+    /// current order.
+    pub fn reorder(mut self, headers: Vec<&str>) -> Result<Self, Box<dyn Error>> {
+        for &header in &headers {
+            if !self.headers.iter().any(|h| h == header) {
+                return Err(format!("cannot reorder unknown column '{}'", header).into());
+            }
+        }
+
+        let step = self.begin_step(format!("reorder {}", headers.join(", ")));
+        let mut order: Vec<String> = Vec::with_capacity(self.headers.len());
+        for header in headers
+            .iter()
+            .map(|h| h.to_string())
+            .chain(self.headers.clone())
+        {
+            if !order.contains(&header) {
+                order.push(header);
+            }
+        }
+
+        let pb = self
+            .progress
+            .start("Reordering columns...", Some(self.records.len() as u64));
+        for record in &mut self.records {
+            let mut reordered = Record::with_capacity(record.len());
+            reordered.set_position(record.position().cloned());
+            for header in &order {
+                if let Some(value) = record.remove(header) {
+                    reordered.insert(header.clone(), value);
+                }
+            }
+            // Columns missing from the headers keep their place after the known ones.
+            for (header, value) in std::mem::take(record) {
+                reordered.insert(header, value);
+            }
+            *record = reordered;
+            pb.inc(1);
+        }
+        self.passthrough = order
+            .iter()
+            .filter(|header| self.passthrough.contains(header))
+            .cloned()
+            .collect();
+        self.headers = order;
+        pb.finish("Finished reordering columns");
+        self.end_step(step);
+        Ok(self)
+    }
+
+    /// Checks and normalizes every value of `column` as `cast`, e.g. `Lec Units` as
+    /// [`CastType::Decimal`].
+    ///
+    /// Values that do not parse are reported like [`Pipeline::try_derive`] failures: the
+    /// step aborts, or under [`crate::ErrorPolicy::Collect`] the value is blanked and logged.
+    pub fn cast(self, column: &str, cast: CastType) -> Result<Self, Box<dyn Error>> {
+        if !self.headers.iter().any(|header| header == column) {
+            return Err(format!("cannot cast unknown column '{}'", column).into());
+        }
+
+        let step = format!("cast {} as {}", column, cast);
+        self.derive_checked(step, column, |record| {
+            cast.apply(record.get(column).map(String::as_str).unwrap_or_default())
+        })
+    }
+}
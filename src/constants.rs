@@ -2,17 +2,15 @@
 pub const HEADER_PROGRAM: &str = "Program";
 pub const HEADER_SEMESTER: &str = "Semester";
 pub const HEADER_COURSE_CODE: &str = "Course Code";
-pub const HEADER_DESCRIPTIVE_TITLE: &str = "Descriptive Title";
-pub const HEADER_INPUT_YEAR: &str = "Year";
 
-// Output CSV Headers
+// Output CSV Headers (also listed in specs/course.toml)
 pub const HEADER_SHORTNAME: &str = "shortname";
 pub const HEADER_FULLNAME: &str = "fullname";
 pub const HEADER_CATEGORY_PATH: &str = "category_path";
 pub const HEADER_STARTDATE: &str = "startdate";
 pub const HEADER_ENDDATE: &str = "enddate";
 pub const HEADER_VISIBLE: &str = "visible";
-pub const HEADER_OUTPUT_YEAR: &str = "year";
+pub const HEADER_YEAR: &str = "year";
 
 // User CSV Headers
 pub const HEADER_USERNAME: &str = "username";
@@ -21,11 +19,15 @@ pub const HEADER_LASTNAME: &str = "lastname";
 pub const HEADER_EMAIL: &str = "email";
 pub const HEADER_PASSWORD: &str = "password";
 
+// Enrolment CSV Headers (long layout; the wide one numbers them from 1)
+pub const HEADER_COURSE: &str = "course";
+pub const HEADER_ROLE: &str = "role";
+
+// Enrolment Roles
+pub const ROLE_STUDENT: &str = "student";
+pub const ROLE_EDITINGTEACHER: &str = "editingteacher";
+
 // Default Values
-pub const DEFAULT_PROGRAM: &str = "GEN";
-pub const VALUE_VISIBLE_TRUE: &str = "1";
-pub const VALUE_ROLE_STUDENT: &str = "student";
-pub const VALUE_ROLE_TEACHER: &str = "editingteacher";
 pub const DEFAULT_PASSWORD: &str = "User123#";
 
 // Semester Identifiers
@@ -38,3 +40,10 @@ pub const DATE_SEM1_START: &str = "08-01";
 pub const DATE_SEM1_END: &str = "12-18";
 pub const DATE_SEM2_START: &str = "01-20";
 pub const DATE_SEM2_END: &str = "06-01";
+
+// Diff Mode
+pub const DIFF_PREVIEW_ROWS: usize = 20;
+pub const HEADER_DIFF_CHANGE: &str = "change";
+pub const HEADER_DIFF_COLUMN: &str = "column";
+pub const HEADER_DIFF_OLD: &str = "old";
+pub const HEADER_DIFF_NEW: &str = "new";
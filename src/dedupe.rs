@@ -0,0 +1,142 @@
+use crate::Pipeline;
+use crate::errors::location;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Which row survives when several rows share the same key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DedupeStrategy {
+    KeepFirst,
+    KeepLast,
+    /// Keep nothing and fail with the [`DedupeReport`].
+    Error,
+}
+
+/// A row whose key was already used by an earlier row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: Vec<String>,
+    /// Location of the first row with this key.
+    pub first: String,
+    /// Location of the duplicate row.
+    pub duplicate: String,
+    /// Columns whose values differ between the two rows.
+    pub differing: Vec<String>,
+}
+
+/// Every duplicate found by [`Pipeline::dedupe_by`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DedupeReport {
+    pub keys: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl DedupeReport {
+    pub fn is_empty(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+impl fmt::Display for DedupeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} duplicate(s) on [{}]:",
+            self.conflicts.len(),
+            self.keys.join(", ")
+        )?;
+        for conflict in &self.conflicts {
+            write!(
+                f,
+                "\n- {} duplicates {} ({})",
+                conflict.duplicate,
+                conflict.first,
+                conflict.key.join(", ")
+            )?;
+            if conflict.differing.is_empty() {
+                write!(f, " identical")?;
+            } else {
+                write!(f, " differs in [{}]", conflict.differing.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for DedupeReport {}
+
+impl Pipeline {
+    /// Keeps one row per distinct value of `keys` and reports every duplicate it saw.
+    ///
+    /// With [`DedupeStrategy::Error`] any duplicate fails the step with the report instead.
+    pub fn dedupe_by(
+        mut self,
+        keys: Vec<&str>,
+        strategy: DedupeStrategy,
+    ) -> Result<(Self, DedupeReport), Box<dyn Error>> {
+        let step = self.begin_step(format!("dedupe_by {}", keys.join(", ")));
+        let pb = self.progress.start(
+            &format!("Deduplicating on {}...", keys.join(", ")),
+            Some(self.records.len() as u64),
+        );
+
+        let mut report = DedupeReport {
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+            conflicts: Vec::new(),
+        };
+        // key -> index of the first row with that key, and of the row that is kept
+        let mut seen: HashMap<Vec<String>, (usize, usize)> = HashMap::new();
+        let mut keep = vec![true; self.records.len()];
+
+        for (idx, record) in self.records.iter().enumerate() {
+            let key: Vec<String> = keys
+                .iter()
+                .map(|&key| record.get(key).cloned().unwrap_or_default())
+                .collect();
+
+            if let Some((first_idx, kept_idx)) = seen.get_mut(&key) {
+                let first = &self.records[*first_idx];
+                let differing = self
+                    .headers
+                    .iter()
+                    .filter(|header| first.get(header) != record.get(header))
+                    .cloned()
+                    .collect();
+                report.conflicts.push(Conflict {
+                    key,
+                    first: location(first, *first_idx),
+                    duplicate: location(record, idx),
+                    differing,
+                });
+
+                if strategy == DedupeStrategy::KeepLast {
+                    keep[*kept_idx] = false;
+                    *kept_idx = idx;
+                } else {
+                    keep[idx] = false;
+                }
+            } else {
+                seen.insert(key, (idx, idx));
+            }
+            pb.inc(1);
+        }
+
+        if strategy == DedupeStrategy::Error && !report.is_empty() {
+            pb.abandon(&format!("Found {} duplicate(s)", report.conflicts.len()));
+            return Err(report.into());
+        }
+
+        let initial_count = self.records.len();
+        let mut flags = keep.into_iter();
+        self.records.retain(|_| flags.next().unwrap_or(true));
+
+        pb.finish(&format!(
+            "Deduplicated records: {} -> {}",
+            initial_count,
+            self.records.len()
+        ));
+        self.end_step(step);
+        Ok((self, report))
+    }
+}
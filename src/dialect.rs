@@ -0,0 +1,83 @@
+use csv::{QuoteStyle, ReaderBuilder, Trim, WriterBuilder};
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// How CSV files are read and written.
+///
+/// Input is transcoded from `encoding` to UTF-8 while it is read; output is always UTF-8.
+#[derive(Clone, Debug)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub quote_style: QuoteStyle,
+    pub encoding: &'static Encoding,
+    /// Drop a byte order mark at the start of the input.
+    pub strip_bom: bool,
+    /// Start the output with a UTF-8 byte order mark, as Excel expects.
+    pub write_bom: bool,
+    /// Trim surrounding whitespace from headers and values while reading.
+    pub trim: bool,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            quote_style: QuoteStyle::Necessary,
+            encoding: UTF_8,
+            strip_bom: true,
+            write_bom: false,
+            trim: false,
+        }
+    }
+}
+
+impl Dialect {
+    /// Looks up an encoding by its WHATWG label, e.g. `utf-8`, `windows-1252` or `latin1`.
+    pub fn encoding_for_label(label: &str) -> Result<&'static Encoding, Box<dyn Error>> {
+        Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| format!("unknown encoding '{}'", label).into())
+    }
+
+    pub fn reader(&self, path: &str) -> Result<csv::Reader<Box<dyn Read>>, Box<dyn Error>> {
+        let file = File::open(path)?;
+
+        let mut decoder = DecodeReaderBytesBuilder::new();
+        decoder.strip_bom(self.strip_bom);
+        if self.encoding == UTF_8 {
+            // Leave UTF-8 untouched so invalid bytes still surface as CSV errors.
+            decoder.utf8_passthru(true);
+        } else {
+            decoder.encoding(Some(self.encoding));
+        }
+        let input: Box<dyn Read> = Box::new(decoder.build(file));
+
+        Ok(ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .trim(if self.trim { Trim::All } else { Trim::None })
+            .from_reader(input))
+    }
+
+    pub fn writer(&self, path: &str) -> Result<csv::Writer<File>, Box<dyn Error>> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(path)?;
+        if self.write_bom {
+            file.write_all(UTF8_BOM)?;
+        }
+
+        Ok(WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quote_style(self.quote_style)
+            .from_writer(file))
+    }
+}
@@ -0,0 +1,185 @@
+use crate::errors::location;
+use crate::{Pipeline, Record};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A column whose value differs between the old and new row with the same key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// A key present on both sides with different values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub key: Vec<String>,
+    pub fields: Vec<FieldChange>,
+}
+
+/// What [`Pipeline::diff`] found between an old and a new set of rows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    pub keys: Vec<String>,
+    /// New rows whose key is not in the old rows, in new order.
+    pub added: Vec<Record>,
+    /// Old rows whose key is not in the new rows, in old order.
+    pub removed: Vec<Record>,
+    /// Keys on both sides whose shared columns differ, in new order.
+    pub changed: Vec<Change>,
+    pub unchanged: usize,
+    /// Columns only the new rows have; their values are not compared.
+    pub added_columns: Vec<String>,
+    /// Columns only the old rows have.
+    pub removed_columns: Vec<String>,
+}
+
+impl DiffReport {
+    /// Whether both sides hold the same rows and columns.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+    }
+
+    /// The counts and the added and removed columns, without the changes themselves.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Diff on [{}]: {} added, {} removed, {} changed, {} unchanged",
+            self.keys.join(", "),
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        );
+        for column in &self.added_columns {
+            summary.push_str(&format!("\n- added column '{}'", column));
+        }
+        for column in &self.removed_columns {
+            summary.push_str(&format!("\n- removed column '{}'", column));
+        }
+        summary
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())?;
+        for change in &self.changed {
+            let fields: Vec<String> = change
+                .fields
+                .iter()
+                .map(|field| format!("{} '{}' -> '{}'", field.column, field.old, field.new))
+                .collect();
+            write!(f, "\n- ({}) {}", change.key.join(", "), fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Pipeline {
+    /// Compares these rows, taken as the old ones, with `other` by the values of `keys`.
+    ///
+    /// Only columns both sides have are compared. Fails if a key column is missing or a
+    /// key is used by more than one row on either side.
+    pub fn diff(&self, other: &Pipeline, keys: Vec<&str>) -> Result<DiffReport, Box<dyn Error>> {
+        let old = index_by(self, &keys, "old")?;
+        index_by(other, &keys, "new")?;
+
+        let compared: Vec<&String> = other
+            .headers
+            .iter()
+            .filter(|header| self.headers.contains(header) && !keys.contains(&header.as_str()))
+            .collect();
+        let mut report = DiffReport {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            added_columns: missing_from(&other.headers, &self.headers),
+            removed_columns: missing_from(&self.headers, &other.headers),
+            ..DiffReport::default()
+        };
+
+        let mut matched = vec![false; self.records.len()];
+        for record in &other.records {
+            let key = key_of(record, &keys);
+            let Some(&idx) = old.get(&key) else {
+                report.added.push(record.clone());
+                continue;
+            };
+            matched[idx] = true;
+
+            let fields: Vec<FieldChange> = compared
+                .iter()
+                .filter_map(|column| {
+                    let old = self.records[idx].get(column).cloned().unwrap_or_default();
+                    let new = record.get(column).cloned().unwrap_or_default();
+                    (old != new).then(|| FieldChange {
+                        column: column.to_string(),
+                        old,
+                        new,
+                    })
+                })
+                .collect();
+            if fields.is_empty() {
+                report.unchanged += 1;
+            } else {
+                report.changed.push(Change { key, fields });
+            }
+        }
+
+        report.removed = self
+            .records
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(record, _)| record.clone())
+            .collect();
+        Ok(report)
+    }
+}
+
+fn key_of(record: &Record, keys: &[&str]) -> Vec<String> {
+    keys.iter()
+        .map(|key| record.get(key).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// Maps each key of `pipeline` to its row, failing on missing key columns and repeated keys.
+fn index_by(
+    pipeline: &Pipeline,
+    keys: &[&str],
+    side: &str,
+) -> Result<HashMap<Vec<String>, usize>, Box<dyn Error>> {
+    if let Some(key) = keys
+        .iter()
+        .find(|key| !pipeline.headers.iter().any(|h| h == *key))
+    {
+        return Err(format!("diff: the {} rows have no column '{}'", side, key).into());
+    }
+    let mut index = HashMap::with_capacity(pipeline.records.len());
+    for (idx, record) in pipeline.records.iter().enumerate() {
+        if let Some(first) = index.insert(key_of(record, keys), idx) {
+            return Err(format!(
+                "diff: {} repeats the key ({}) of {} in the {} rows; add key columns",
+                location(record, idx),
+                key_of(record, keys).join(", "),
+                location(&pipeline.records[first], first),
+                side
+            )
+            .into());
+        }
+    }
+    Ok(index)
+}
+
+/// Headers in `headers` that `other` does not have.
+fn missing_from(headers: &[String], other: &[String]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|header| !other.contains(header))
+        .cloned()
+        .collect()
+}
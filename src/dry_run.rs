@@ -1,5 +1,8 @@
 use crate::constants::*;
-use moodle_course_builder::Record;
+use moodle_course_builder::{DiffReport, ErrorLog, Lineage, Record, ValidationReport};
+
+/// Row number, missing headers, shortname and fullname of a record with missing values.
+type MissingRow<'a> = (usize, Vec<&'a str>, Option<String>, Option<String>);
 
 pub fn print_preview_table(records: &[Record], headers: &[&str], limit: usize) {
     if records.is_empty() || limit == 0 {
@@ -44,6 +47,79 @@ pub fn print_preview_table(records: &[Record], headers: &[&str], limit: usize) {
     }
 }
 
+/// Prints the summary of a diff and up to `limit` rows each of added and removed rows
+/// and of changed fields.
+pub fn print_diff(
+    report: &DiffReport,
+    old_headers: &[String],
+    new_headers: &[String],
+    limit: usize,
+) {
+    println!("{}", report.summary());
+
+    let sections = [
+        ("Added", &report.added, new_headers),
+        ("Removed", &report.removed, old_headers),
+    ];
+    for (title, records, headers) in sections {
+        if records.is_empty() {
+            continue;
+        }
+        println!("{} ({}):", title, records.len());
+        let headers: Vec<&str> = headers.iter().map(String::as_str).collect();
+        print_preview_table(records, &headers, limit);
+    }
+
+    if report.changed.is_empty() {
+        return;
+    }
+    let mut headers: Vec<&str> = report.keys.iter().map(String::as_str).collect();
+    headers.extend([HEADER_DIFF_COLUMN, HEADER_DIFF_OLD, HEADER_DIFF_NEW]);
+    let rows: Vec<Record> = diff_rows(report)
+        .into_iter()
+        .filter(|row| {
+            row.get(HEADER_DIFF_CHANGE)
+                .is_some_and(|change| change == "changed")
+        })
+        .collect();
+    println!("Changed ({}):", report.changed.len());
+    print_preview_table(&rows, &headers, limit);
+}
+
+/// One row per added or removed key and per changed field, keyed like the compared rows.
+pub fn diff_rows(report: &DiffReport) -> Vec<Record> {
+    let keyed = |change: &str, record: &Record| {
+        let mut row = Record::new();
+        row.insert(HEADER_DIFF_CHANGE.to_string(), change.to_string());
+        for key in &report.keys {
+            let value = record.get(key).cloned().unwrap_or_default();
+            row.insert(key.clone(), value);
+        }
+        for header in [HEADER_DIFF_COLUMN, HEADER_DIFF_OLD, HEADER_DIFF_NEW] {
+            row.insert(header.to_string(), String::new());
+        }
+        row
+    };
+
+    let mut rows = Vec::new();
+    rows.extend(report.added.iter().map(|record| keyed("added", record)));
+    rows.extend(report.removed.iter().map(|record| keyed("removed", record)));
+    for change in &report.changed {
+        for field in &change.fields {
+            let mut row = Record::new();
+            row.insert(HEADER_DIFF_CHANGE.to_string(), "changed".to_string());
+            for (key, value) in report.keys.iter().zip(&change.key) {
+                row.insert(key.clone(), value.clone());
+            }
+            row.insert(HEADER_DIFF_COLUMN.to_string(), field.column.clone());
+            row.insert(HEADER_DIFF_OLD.to_string(), field.old.clone());
+            row.insert(HEADER_DIFF_NEW.to_string(), field.new.clone());
+            rows.push(row);
+        }
+    }
+    rows
+}
+
 pub fn report_missing(records: &[Record], headers: &[&str]) {
     if records.is_empty() {
         println!("No records to check for missing values.");
@@ -51,7 +127,7 @@ pub fn report_missing(records: &[Record], headers: &[&str]) {
     }
 
     let mut missing_counts = vec![0usize; headers.len()];
-    let mut missing_rows: Vec<(usize, Vec<&str>, Option<String>, Option<String>)> = Vec::new();
+    let mut missing_rows: Vec<MissingRow> = Vec::new();
 
     for (idx, record) in records.iter().enumerate() {
         let mut missing_headers = Vec::new();
@@ -83,16 +159,38 @@ pub fn report_missing(records: &[Record], headers: &[&str]) {
     println!("Records with missing values:");
     for (row_idx, missing_headers, shortname, fullname) in missing_rows {
         let mut details = format!("Row {}: missing [{}]", row_idx, missing_headers.join(", "));
-        if let Some(value) = shortname {
-            if !value.trim().is_empty() {
-                details.push_str(&format!(" shortname={}", value));
-            }
+        if let Some(value) = shortname
+            && !value.trim().is_empty()
+        {
+            details.push_str(&format!(" shortname={}", value));
         }
-        if let Some(value) = fullname {
-            if !value.trim().is_empty() {
-                details.push_str(&format!(" fullname={}", value));
-            }
+        if let Some(value) = fullname
+            && !value.trim().is_empty()
+        {
+            details.push_str(&format!(" fullname={}", value));
         }
         println!("{}", details);
     }
 }
+
+pub fn report_errors(errors: &ErrorLog) {
+    if errors.is_empty() {
+        return;
+    }
+    println!("{}", errors);
+}
+
+pub fn report_validation(reports: &[ValidationReport]) {
+    for report in reports {
+        if !report.is_empty() {
+            println!("{}", report);
+        }
+    }
+}
+
+pub fn report_lineage(lineage: &Lineage) {
+    if lineage.is_empty() {
+        return;
+    }
+    println!("{}", lineage);
+}
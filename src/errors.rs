@@ -0,0 +1,96 @@
+use crate::Record;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// What a fallible step does when a row fails.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop at the first failing row.
+    Abort,
+    /// Leave the value blank, log the error and keep going.
+    #[default]
+    Collect,
+}
+
+/// A failure tied to one row and column of the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowError {
+    pub path: Option<String>,
+    pub line: Option<u64>,
+    /// 1-based index of the record within the step, used when the line is unknown.
+    pub record: usize,
+    pub column: String,
+    pub message: String,
+}
+
+/// Describes where `record` came from: `path:line` when known, `record N` otherwise.
+pub fn location(record: &Record, index: usize) -> String {
+    match record.position() {
+        Some(position) => format!("{}:{}", position.path, position.line),
+        None => format!("record {}", index + 1),
+    }
+}
+
+impl RowError {
+    pub fn new(record: &Record, index: usize, column: &str, message: String) -> Self {
+        let position = record.position();
+        Self {
+            path: position.map(|p| p.path.to_string()),
+            line: position.map(|p| p.line),
+            record: index + 1,
+            column: column.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => write!(f, "{}:{}", path, line)?,
+            _ => write!(f, "record {}", self.record)?,
+        }
+        write!(f, " [{}] {}", self.column, self.message)
+    }
+}
+
+impl Error for RowError {}
+
+/// Shared, append-only list of [`RowError`]s collected while a pipeline runs.
+///
+/// Cloning the log hands out another handle to the same list, so it can be kept
+/// after the pipeline itself is consumed by `to_file`.
+#[derive(Clone, Debug, Default)]
+pub struct ErrorLog {
+    errors: Arc<Mutex<Vec<RowError>>>,
+}
+
+impl ErrorLog {
+    pub fn push(&self, error: RowError) {
+        self.errors.lock().unwrap().push(error);
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn errors(&self) -> Vec<RowError> {
+        self.errors.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Display for ErrorLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.errors.lock().unwrap();
+        write!(f, "{} row error(s):", errors.len())?;
+        for error in errors.iter() {
+            write!(f, "\n- {}", error)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{Pipeline, Record, headers_of};
+
+impl Pipeline {
+    /// Replaces every record with the records `expand` returns for it, which may be none.
+    ///
+    /// Produced records without a position inherit the position of their source row, and
+    /// new columns are appended to the header order as they first appear.
+    pub fn flat_map<F>(self, expand: F) -> Self
+    where
+        F: Fn(&Record) -> Vec<Record>,
+    {
+        self.expand("flat_map".to_string(), expand)
+    }
+
+    fn expand<F>(mut self, step: String, expand: F) -> Self
+    where
+        F: Fn(&Record) -> Vec<Record>,
+    {
+        let step = self.begin_step(step);
+        let pb = self
+            .progress
+            .start("Expanding records...", Some(self.records.len() as u64));
+
+        let initial_count = self.records.len();
+        let mut expanded = Vec::with_capacity(initial_count);
+        for record in &self.records {
+            for mut new_record in expand(record) {
+                if new_record.position().is_none() {
+                    new_record.set_position(record.position().cloned());
+                }
+                expanded.push(new_record);
+            }
+            pb.inc(1);
+        }
+
+        for header in headers_of(&expanded) {
+            if !self.headers.contains(&header) {
+                self.headers.push(header);
+            }
+        }
+        self.records = expanded;
+
+        pb.finish(&format!(
+            "Expanded records: {} -> {}",
+            initial_count,
+            self.records.len()
+        ));
+        self.end_step(step);
+        self
+    }
+
+    /// Splits `column` on `delimiter` and emits one copy of the row per trimmed, non-blank
+    /// part. Rows with nothing to split are kept once, unchanged.
+    pub fn explode(self, column: &str, delimiter: &str) -> Self {
+        let step = format!("explode {}", column);
+        self.expand(step, |record| {
+            let parts: Vec<&str> = record
+                .get(column)
+                .map(|value| value.split(delimiter).map(str::trim).collect())
+                .unwrap_or_default();
+            let parts: Vec<&str> = parts.into_iter().filter(|p| !p.is_empty()).collect();
+            if parts.is_empty() {
+                return vec![record.clone()];
+            }
+            parts
+                .into_iter()
+                .map(|part| {
+                    let mut new_record = record.clone();
+                    new_record.insert(column.to_string(), part.to_string());
+                    new_record
+                })
+                .collect()
+        })
+    }
+}
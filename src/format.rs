@@ -0,0 +1,216 @@
+use crate::{Position, Record, headers_of};
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// File layout read by `from_file` and written by `to_file`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    Csv,
+    /// A single JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl FileFormat {
+    /// Picks the format from the extension: `.json`, `.ndjson`/`.jsonl`, anything else is CSV.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("json") => FileFormat::Json,
+            Some("ndjson") | Some("jsonl") => FileFormat::Ndjson,
+            _ => FileFormat::Csv,
+        }
+    }
+}
+
+/// Strings stay as they are, `null` becomes blank and nested values keep their JSON text.
+fn value_to_string(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value,
+        Value::Bool(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        nested => nested.to_string(),
+    }
+}
+
+fn object_to_record(value: Value, source: &str) -> Result<Record, Box<dyn Error>> {
+    match value {
+        Value::Object(map) => Ok(map
+            .into_iter()
+            .map(|(key, value)| (key, value_to_string(value)))
+            .collect()),
+        other => Err(format!("{}: expected a JSON object, found {}", source, other).into()),
+    }
+}
+
+/// Reads a JSON array or NDJSON file. Headers follow the key order of the objects.
+///
+/// NDJSON records remember their line; records from an array have no position.
+pub(crate) fn read_json(
+    path: &str,
+    format: FileFormat,
+) -> Result<(Vec<String>, Vec<Record>), Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+
+    if format == FileFormat::Ndjson {
+        let source: Arc<str> = Arc::from(path);
+        for (idx, line) in reader.lines().enumerate() {
+            if let Some(record) = ndjson_record(&line?, idx, &source)? {
+                records.push(record);
+            }
+        }
+    } else {
+        match serde_json::from_reader(reader)? {
+            Value::Array(values) => {
+                for value in values {
+                    records.push(object_to_record(value, path)?);
+                }
+            }
+            other => return Err(format!("{}: expected a JSON array, found {}", path, other).into()),
+        }
+    }
+
+    Ok((headers_of(&records), records))
+}
+
+/// Records of an NDJSON file, parsed as they are pulled.
+pub(crate) type NdjsonRows = Box<dyn Iterator<Item = Result<Record, Box<dyn Error>>>>;
+
+/// Reads an NDJSON file one line at a time. Headers come from the first object, and a later
+/// object with a key the first one lacks fails its row.
+pub(crate) fn ndjson_rows(path: &str) -> Result<(Vec<String>, NdjsonRows), Box<dyn Error>> {
+    let source: Arc<str> = Arc::from(path);
+    let mut lines = BufReader::new(File::open(path)?).lines().enumerate();
+    let mut first = None;
+    for (idx, line) in lines.by_ref() {
+        if let Some(record) = ndjson_record(&line?, idx, &source)? {
+            first = Some(record);
+            break;
+        }
+    }
+    let headers: Vec<String> = first
+        .iter()
+        .flat_map(|record| record.keys().cloned())
+        .collect();
+
+    let expected = headers.clone();
+    let rest = lines.filter_map(move |(idx, line)| {
+        let record = match line {
+            Ok(line) => ndjson_record(&line, idx, &source).transpose()?,
+            Err(err) => Err(err.into()),
+        };
+        Some(record.and_then(|record| {
+            if let Some(key) = record.keys().find(|key| !expected.contains(key)) {
+                return Err(format!(
+                    "{}:{}: key '{}' is not in the first object",
+                    source,
+                    idx + 1,
+                    key
+                )
+                .into());
+            }
+            Ok(record)
+        }))
+    });
+    Ok((headers, Box::new(first.map(Ok).into_iter().chain(rest))))
+}
+
+/// Parses line `idx` (0-based) of an NDJSON file; blank lines hold no record.
+fn ndjson_record(
+    line: &str,
+    idx: usize,
+    source: &Arc<str>,
+) -> Result<Option<Record>, Box<dyn Error>> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let location = format!("{}:{}", source, idx + 1);
+    let value: Value =
+        serde_json::from_str(line).map_err(|err| format!("{}: {}", location, err))?;
+    let mut record = object_to_record(value, &location)?;
+    record.set_position(Some(Position {
+        path: Arc::clone(source),
+        line: idx as u64 + 1,
+    }));
+    Ok(Some(record))
+}
+
+/// `true` and `false` become booleans and numbers that read back unchanged become numbers;
+/// everything else, including blanks and zero-padded codes such as `007`, stays a string.
+fn json_value(value: String) -> Value {
+    match value.as_str() {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+    match serde_json::from_str::<serde_json::Number>(&value) {
+        Ok(number) if number.to_string() == value => Value::Number(number),
+        _ => Value::String(value),
+    }
+}
+
+/// Writes rows as JSON objects keyed by the output columns. See [`json_value`] for how
+/// values are typed.
+pub(crate) struct JsonWriter {
+    out: BufWriter<File>,
+    columns: Vec<String>,
+    array: bool,
+    rows: usize,
+}
+
+impl JsonWriter {
+    pub fn create(file: File, columns: Vec<String>, array: bool) -> Result<Self, Box<dyn Error>> {
+        let mut out = BufWriter::new(file);
+        if array {
+            out.write_all(b"[")?;
+        }
+        Ok(Self {
+            out,
+            columns,
+            array,
+            rows: 0,
+        })
+    }
+
+    pub fn write_row<I>(&mut self, values: I) -> Result<(), Box<dyn Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let object: Map<String, Value> = self
+            .columns
+            .iter()
+            .cloned()
+            .zip(values.into_iter().map(json_value))
+            .collect();
+
+        if self.array {
+            self.out
+                .write_all(if self.rows == 0 { b"\n  " } else { b",\n  " })?;
+        }
+        serde_json::to_writer(&mut self.out, &object)?;
+        if !self.array {
+            self.out.write_all(b"\n")?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        if self.array {
+            self.out
+                .write_all(if self.rows == 0 { b"]\n" } else { b"\n]\n" })?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
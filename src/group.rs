@@ -0,0 +1,244 @@
+use crate::{ErrorPolicy, Pipeline, Record, RowError};
+use indexmap::IndexMap;
+use std::cmp::Ordering;
+use std::error::Error;
+
+/// How the values of one column are folded into a single value per group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AggregateOp {
+    /// Number of rows in the group.
+    Count,
+    /// Numeric sum; blank values are ignored.
+    Sum(String),
+    /// Smallest value, compared numerically when every value of the column is a number and
+    /// as text otherwise.
+    Min(String),
+    /// Largest value, compared like [`AggregateOp::Min`].
+    Max(String),
+    /// Value of the first row in the group.
+    First(String),
+    /// Non-blank values joined with the separator.
+    Join(String, String),
+}
+
+/// An [`AggregateOp`] and the header its result is written to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Aggregate {
+    pub output: String,
+    pub op: AggregateOp,
+}
+
+impl Aggregate {
+    pub fn count(output: &str) -> Self {
+        Self::new(output, AggregateOp::Count)
+    }
+
+    pub fn sum(column: &str, output: &str) -> Self {
+        Self::new(output, AggregateOp::Sum(column.to_string()))
+    }
+
+    pub fn min(column: &str, output: &str) -> Self {
+        Self::new(output, AggregateOp::Min(column.to_string()))
+    }
+
+    pub fn max(column: &str, output: &str) -> Self {
+        Self::new(output, AggregateOp::Max(column.to_string()))
+    }
+
+    pub fn first(column: &str, output: &str) -> Self {
+        Self::new(output, AggregateOp::First(column.to_string()))
+    }
+
+    pub fn join(column: &str, separator: &str, output: &str) -> Self {
+        Self::new(
+            output,
+            AggregateOp::Join(column.to_string(), separator.to_string()),
+        )
+    }
+
+    fn new(output: &str, op: AggregateOp) -> Self {
+        Self {
+            output: output.to_string(),
+            op,
+        }
+    }
+}
+
+/// Rows of a [`Pipeline`] grouped by key columns, waiting for [`GroupBy::aggregate`].
+pub struct GroupBy {
+    pipeline: Pipeline,
+    keys: Vec<String>,
+}
+
+/// Compares numerically if `numeric`, which holds when every value of the column parses
+/// as a number, and as strings otherwise.
+fn compare_values(a: &str, b: &str, numeric: bool) -> Ordering {
+    match (numeric, a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (true, Ok(x), Ok(y)) => x.total_cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+/// Whether every non-blank value of `column` is a number.
+fn is_numeric(records: &[Record], column: &str) -> bool {
+    records
+        .iter()
+        .filter_map(|record| record.get(column))
+        .filter(|value| !value.trim().is_empty())
+        .all(|value| value.trim().parse::<f64>().is_ok())
+}
+
+/// Formats a sum without a trailing `.0` when it is a whole number.
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+impl Pipeline {
+    /// Groups rows sharing the same values in `keys`, in order of first appearance.
+    pub fn group_by(self, keys: Vec<&str>) -> GroupBy {
+        GroupBy {
+            pipeline: self,
+            keys: keys.into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+impl GroupBy {
+    /// Builds one row per group with the key columns followed by each aggregate's output.
+    ///
+    /// Non-numeric values in a [`AggregateOp::Sum`] column are reported like
+    /// [`Pipeline::try_derive`] failures and left out of the sum. Fails if a key column is
+    /// missing or an aggregate's output is named like a key or another output.
+    pub fn aggregate(self, aggregates: Vec<Aggregate>) -> Result<Pipeline, Box<dyn Error>> {
+        let GroupBy { pipeline, keys } = self;
+        if let Some(key) = keys.iter().find(|key| !pipeline.headers.contains(key)) {
+            return Err(format!("group_by: the rows have no column '{}'", key).into());
+        }
+        for (idx, aggregate) in aggregates.iter().enumerate() {
+            if keys.contains(&aggregate.output)
+                || aggregates[..idx]
+                    .iter()
+                    .any(|other| other.output == aggregate.output)
+            {
+                return Err(format!(
+                    "group_by: aggregate output '{}' is already a column of the groups",
+                    aggregate.output
+                )
+                .into());
+            }
+        }
+        let numeric: Vec<bool> = aggregates
+            .iter()
+            .map(|aggregate| match &aggregate.op {
+                AggregateOp::Min(column) | AggregateOp::Max(column) => {
+                    is_numeric(&pipeline.records, column)
+                }
+                _ => false,
+            })
+            .collect();
+        let step = pipeline.begin_step(format!("group_by {}", keys.join(", ")));
+
+        let pb = pipeline.progress.start(
+            &format!("Grouping by {}...", keys.join(", ")),
+            Some(pipeline.records.len() as u64),
+        );
+
+        // Rows keep their index in the pipeline so errors point at the right record.
+        let mut groups: IndexMap<Vec<String>, Vec<(usize, &Record)>> = IndexMap::new();
+        for (idx, record) in pipeline.records.iter().enumerate() {
+            let key = keys
+                .iter()
+                .map(|key| record.get(key).cloned().unwrap_or_default())
+                .collect();
+            groups.entry(key).or_default().push((idx, record));
+            pb.inc(1);
+        }
+
+        let mut records = Vec::with_capacity(groups.len());
+        for (key, rows) in &groups {
+            let mut record: Record = keys.iter().cloned().zip(key.iter().cloned()).collect();
+            for (aggregate, &numeric) in aggregates.iter().zip(&numeric) {
+                let value = match &aggregate.op {
+                    AggregateOp::Count => rows.len().to_string(),
+                    AggregateOp::Sum(column) => {
+                        let mut total = 0.0;
+                        for &(idx, row) in rows {
+                            let value = row.get(column).map(|v| v.trim()).unwrap_or("");
+                            if value.is_empty() {
+                                continue;
+                            }
+                            match value.parse::<f64>() {
+                                Ok(number) => total += number,
+                                Err(_) => {
+                                    let message = format!("cannot sum non-numeric '{}'", value);
+                                    let error = RowError::new(row, idx, column, message);
+                                    if pipeline.policy == ErrorPolicy::Abort {
+                                        pb.abandon("Failed grouping records");
+                                        return Err(error.into());
+                                    }
+                                    pipeline.errors.push(error);
+                                }
+                            }
+                        }
+                        format_number(total)
+                    }
+                    AggregateOp::Min(column) | AggregateOp::Max(column) => {
+                        let values = rows
+                            .iter()
+                            .filter_map(|(_, row)| row.get(column))
+                            .filter(|value| !value.trim().is_empty());
+                        let chosen = if matches!(aggregate.op, AggregateOp::Min(_)) {
+                            values.min_by(|a, b| compare_values(a, b, numeric))
+                        } else {
+                            values.max_by(|a, b| compare_values(a, b, numeric))
+                        };
+                        chosen.cloned().unwrap_or_default()
+                    }
+                    AggregateOp::First(column) => rows
+                        .first()
+                        .and_then(|(_, row)| row.get(column))
+                        .cloned()
+                        .unwrap_or_default(),
+                    AggregateOp::Join(column, separator) => rows
+                        .iter()
+                        .filter_map(|(_, row)| row.get(column))
+                        .filter(|value| !value.trim().is_empty())
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(separator),
+                };
+                record.insert(aggregate.output.clone(), value);
+            }
+            records.push(record);
+        }
+
+        pb.finish(&format!(
+            "Grouped records: {} -> {} groups",
+            pipeline.records.len(),
+            records.len()
+        ));
+
+        let mut headers = keys;
+        headers.extend(aggregates.into_iter().map(|aggregate| aggregate.output));
+        let grouped = Pipeline {
+            records,
+            headers,
+            passthrough: Vec::new(),
+            policy: pipeline.policy,
+            errors: pipeline.errors.clone(),
+            write_options: pipeline.write_options.clone(),
+            dialect: pipeline.dialect.clone(),
+            sheet: pipeline.sheet.clone(),
+            source_column: pipeline.source_column.clone(),
+            progress: pipeline.progress.clone(),
+            parallel: pipeline.parallel,
+            lineage: pipeline.lineage.clone(),
+        };
+        grouped.end_step(step);
+        Ok(grouped)
+    }
+}
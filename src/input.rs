@@ -0,0 +1,56 @@
+use crate::lineage::column_changes;
+use std::error::Error;
+
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// Expands glob patterns such as `users/ucmn/faculties/*.csv` to the files they match,
+/// sorted by path. Arguments without glob characters are kept as given.
+pub fn expand_paths(patterns: &[&str]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(GLOB_CHARS) {
+            paths.push(pattern.to_string());
+            continue;
+        }
+
+        let mut matched = Vec::new();
+        for entry in glob::glob(pattern)? {
+            let path = entry?;
+            if path.is_file() {
+                matched.push(path.to_string_lossy().into_owned());
+            }
+        }
+        if matched.is_empty() {
+            return Err(format!("no files match '{}'", pattern).into());
+        }
+        matched.sort();
+        paths.extend(matched);
+    }
+
+    if paths.is_empty() {
+        return Err("no input files given".into());
+    }
+    Ok(paths)
+}
+
+/// Fails unless `path` has the same columns as `first`, in any order.
+pub(crate) fn check_headers(
+    first: &str,
+    expected: &[String],
+    path: &str,
+    headers: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let (unexpected, missing) = column_changes(expected, headers);
+    if unexpected.is_empty() && missing.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = format!("{}: columns differ from {}", path, first);
+    if !missing.is_empty() {
+        message.push_str(&format!("; missing [{}]", missing.join(", ")));
+    }
+    if !unexpected.is_empty() {
+        message.push_str(&format!("; unexpected [{}]", unexpected.join(", ")));
+    }
+    Err(message.into())
+}
@@ -0,0 +1,133 @@
+use crate::{Pipeline, Record};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+/// Suffix added to a right-hand column whose name is already used on the left.
+pub const JOIN_CONFLICT_SUFFIX: &str = "_right";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JoinKind {
+    /// One row per matching pair; unmatched rows on either side are dropped.
+    Inner,
+    /// Every left row, with the right-hand columns left blank when there is no match.
+    Left,
+    /// Only the left rows without a match, unchanged.
+    Anti,
+}
+
+/// The key of `record`, or `None` when any key column is blank, so it matches nothing.
+fn key_of(record: &Record, keys: &[&str]) -> Option<Vec<String>> {
+    keys.iter()
+        .map(|&key| {
+            record
+                .get(key)
+                .filter(|value| !value.trim().is_empty())
+                .cloned()
+        })
+        .collect()
+}
+
+impl Pipeline {
+    /// Joins `other` onto this pipeline where all `keys` columns are equal.
+    ///
+    /// Right-hand key columns are not repeated; other right-hand columns whose name already
+    /// exists on the left get [`JOIN_CONFLICT_SUFFIX`] appended. Rows with a blank key
+    /// column match nothing. Fails if a key column is missing on either side.
+    pub fn join(
+        mut self,
+        other: &Pipeline,
+        keys: Vec<&str>,
+        kind: JoinKind,
+    ) -> Result<Self, Box<dyn Error>> {
+        for (side, headers) in [("left", &self.headers), ("right", &other.headers)] {
+            if let Some(key) = keys.iter().find(|key| !headers.iter().any(|h| h == *key)) {
+                return Err(format!("join: the {} rows have no column '{}'", side, key).into());
+            }
+        }
+
+        let step = self.begin_step(format!("join {:?} on {}", kind, keys.join(", ")));
+        let pb = self.progress.start(
+            &format!("Joining on {}...", keys.join(", ")),
+            Some(self.records.len() as u64),
+        );
+
+        let mut index: HashMap<Vec<String>, Vec<&Record>> = HashMap::new();
+        for record in &other.records {
+            if let Some(key) = key_of(record, &keys) {
+                index.entry(key).or_default().push(record);
+            }
+        }
+
+        // (right header, header in the joined output)
+        let right_columns: Vec<(String, String)> = other
+            .headers
+            .iter()
+            .filter(|header| !keys.contains(&header.as_str()))
+            .map(|header| {
+                let name = if self.headers.contains(header) {
+                    format!("{}{}", header, JOIN_CONFLICT_SUFFIX)
+                } else {
+                    header.clone()
+                };
+                (header.clone(), name)
+            })
+            .collect();
+
+        let initial_count = self.records.len();
+        let mut unmatched_left = 0usize;
+        let mut used_keys: HashSet<Vec<String>> = HashSet::new();
+        let mut joined = Vec::with_capacity(initial_count);
+
+        for record in std::mem::take(&mut self.records) {
+            let key = key_of(&record, &keys);
+            let matches = key.as_ref().and_then(|key| index.get(key));
+            if matches.is_none() {
+                unmatched_left += 1;
+            }
+
+            match (kind, matches) {
+                (JoinKind::Anti, None) => joined.push(record),
+                (JoinKind::Anti, Some(_)) => {}
+                (JoinKind::Left, None) => {
+                    let mut new_record = record;
+                    for (_, name) in &right_columns {
+                        new_record.insert(name.clone(), String::new());
+                    }
+                    joined.push(new_record);
+                }
+                (JoinKind::Inner, None) => {}
+                (JoinKind::Inner | JoinKind::Left, Some(matches)) => {
+                    for right in matches {
+                        let mut new_record = record.clone();
+                        for (header, name) in &right_columns {
+                            let value = right.get(header).cloned().unwrap_or_default();
+                            new_record.insert(name.clone(), value);
+                        }
+                        joined.push(new_record);
+                    }
+                }
+            }
+            if let (Some(key), Some(_)) = (key, matches) {
+                used_keys.insert(key);
+            }
+            pb.inc(1);
+        }
+
+        let unused_right = index.len() - used_keys.len();
+        self.records = joined;
+        if kind != JoinKind::Anti {
+            self.headers
+                .extend(right_columns.into_iter().map(|(_, name)| name));
+        }
+
+        pb.finish(&format!(
+            "Joined records: {} -> {} ({} left rows unmatched, {} right keys unused)",
+            initial_count,
+            self.records.len(),
+            unmatched_left,
+            unused_right
+        ));
+        self.end_step(step);
+        Ok(self)
+    }
+}
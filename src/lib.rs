@@ -1,198 +1,555 @@
-use csv::{ReaderBuilder, WriterBuilder};
-use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashMap;
+use csv::StringRecord;
 use std::error::Error;
-use std::fs::File;
+use std::sync::Arc;
 
-pub type Record = HashMap<String, String>;
+pub mod columns;
+pub mod dedupe;
+pub mod dialect;
+pub mod diff;
+pub mod errors;
+pub mod explode;
+pub mod format;
+pub mod group;
+pub mod input;
+pub mod join;
+pub mod lineage;
+mod parallel;
+pub mod pivot;
+pub mod progress;
+pub mod record;
+pub mod sample;
+pub mod schema;
+pub mod sort;
+pub mod split;
+pub mod spreadsheet;
+pub mod stream;
+pub mod template;
+pub mod typed;
+pub mod writer;
+
+pub use columns::CastType;
+pub use dedupe::{Conflict, DedupeReport, DedupeStrategy};
+pub use dialect::Dialect;
+pub use diff::{Change, DiffReport, FieldChange};
+pub use errors::{ErrorLog, ErrorPolicy, RowError};
+pub use format::FileFormat;
+pub use group::{Aggregate, AggregateOp, GroupBy};
+pub use input::expand_paths;
+pub use join::{JOIN_CONFLICT_SUFFIX, JoinKind};
+pub use lineage::{Lineage, StepRecord};
+pub use progress::{BarProgress, JsonProgress, ProgressSink, SilentProgress, StepProgress};
+pub use record::{Position, Record};
+pub use schema::{ColumnRule, Schema, ValidationReport};
+pub use sort::{SortKey, natural_cmp};
+pub use spreadsheet::{SheetOptions, is_spreadsheet};
+pub use stream::RecordStream;
+pub use template::Template;
+pub use typed::TypedPipeline;
+pub use writer::{REJECT_REASON_HEADER, WriteOptions};
+
+use input::check_headers;
+use lineage::StepTimer;
+use writer::OutputWriter;
 
 pub struct Pipeline {
     records: Vec<Record>,
+    headers: Vec<String>,
+    passthrough: Vec<String>,
+    policy: ErrorPolicy,
+    errors: ErrorLog,
+    write_options: WriteOptions,
+    dialect: Dialect,
+    sheet: SheetOptions,
+    source_column: Option<String>,
+    progress: Arc<dyn ProgressSink>,
+    parallel: bool,
+    lineage: Lineage,
+}
+
+fn record_from_row(headers: &StringRecord, row: &StringRecord, path: &Arc<str>) -> Record {
+    let mut record: Record = headers
+        .iter()
+        .zip(row.iter())
+        .map(|(header, value)| (header.to_string(), value.to_string()))
+        .collect();
+    record.set_position(row.position().map(|p| Position {
+        path: Arc::clone(path),
+        line: p.line(),
+    }));
+    record
+}
+
+/// Collects the header order of `records`, in order of first appearance.
+fn headers_of(records: &[Record]) -> Vec<String> {
+    let mut headers: Vec<String> = Vec::new();
+    for record in records {
+        for key in record.keys() {
+            if !headers.contains(key) {
+                headers.push(key.clone());
+            }
+        }
+    }
+    headers
 }
 
-fn default_style() -> ProgressStyle {
-    ProgressStyle::with_template(
-        "{msg:>30.cyan.bold} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})",
-    )
-    .unwrap()
-    .progress_chars("=>-")
+/// Every column of `current` that is not listed in `selected`, in `current` order.
+fn passthrough_of(current: &[String], selected: &[&str]) -> Vec<String> {
+    current
+        .iter()
+        .filter(|header| !selected.contains(&header.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Progress message printed once `to_file` is done.
+fn finish_message(verb: &str, path: &str, writer: &OutputWriter, options: &WriteOptions) -> String {
+    let mut message = format!("Finished {} to {} (written {}", verb, path, writer.written);
+    if writer.skipped > 0 {
+        message.push_str(&format!(", skipped {} incomplete", writer.skipped));
+        if let Some(rejects_path) = &options.rejects_path {
+            message.push_str(&format!(" -> {}", rejects_path));
+        }
+    }
+    message.push(')');
+    message
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Pipeline {
     pub fn new() -> Self {
         Self {
             records: Vec::new(),
+            headers: Vec::new(),
+            passthrough: Vec::new(),
+            policy: ErrorPolicy::default(),
+            errors: ErrorLog::default(),
+            write_options: WriteOptions::default(),
+            dialect: Dialect::default(),
+            sheet: SheetOptions::default(),
+            source_column: None,
+            progress: Arc::new(BarProgress),
+            parallel: false,
+            lineage: Lineage::default(),
         }
     }
 
     pub fn from_records(records: Vec<Record>) -> Self {
-        Self { records }
+        let headers = headers_of(&records);
+        Self {
+            records,
+            headers,
+            passthrough: Vec::new(),
+            policy: ErrorPolicy::default(),
+            errors: ErrorLog::default(),
+            write_options: WriteOptions::default(),
+            dialect: Dialect::default(),
+            sheet: SheetOptions::default(),
+            source_column: None,
+            progress: Arc::new(BarProgress),
+            parallel: false,
+            lineage: Lineage::default(),
+        }
     }
 
     pub fn len(&self) -> usize {
         self.records.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
     pub fn records(&self) -> &[Record] {
         &self.records
     }
 
+    /// Current column order: input header order, followed by derived columns.
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// Sets how [`Pipeline::try_derive`] handles failing rows.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Writes rows that `to_file` skips to `path`, with a [`REJECT_REASON_HEADER`] column.
+    pub fn rejects_to(mut self, path: &str) -> Self {
+        self.write_options.rejects_path = Some(path.to_string());
+        self
+    }
+
+    /// Makes `to_file` fail on the first incomplete row instead of skipping it.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.write_options.strict = strict;
+        self
+    }
+
+    /// Makes `to_file` write incomplete rows instead of skipping them, such as for a review
+    /// file where those rows matter most.
+    pub fn keep_incomplete(mut self, keep: bool) -> Self {
+        self.write_options.keep_incomplete = keep;
+        self
+    }
+
+    /// Sets the sheet and header row used when `from_file` reads a workbook.
+    pub fn sheet(mut self, sheet: SheetOptions) -> Self {
+        self.sheet = sheet;
+        self
+    }
+
+    /// Makes `from_file` and `from_files` add a `header` column holding each row's file path.
+    pub fn source_column(mut self, header: &str) -> Self {
+        self.source_column = Some(header.to_string());
+        self
+    }
+
+    /// Sets the CSV dialect used by the next `from_file` or `to_file`.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Overrides the output format that `to_file` would pick from the extension.
+    pub fn format(mut self, format: FileFormat) -> Self {
+        self.write_options.format = Some(format);
+        self
+    }
+
+    /// Sets where steps report progress; [`BarProgress`] by default.
+    pub fn progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = sink;
+        self
+    }
+
+    /// Runs `filter`, `derive` and `try_derive` on all cores through rayon. Row order and
+    /// error order stay the same as a sequential run.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Handle to the row errors collected so far; stays valid after the pipeline is consumed.
+    pub fn error_log(&self) -> ErrorLog {
+        self.errors.clone()
+    }
+
+    /// Handle to the steps run so far; stays valid after the pipeline is consumed.
+    pub fn lineage(&self) -> Lineage {
+        self.lineage.clone()
+    }
+
+    /// Starts timing `step` against the current rows and columns.
+    fn begin_step(&self, step: impl Into<String>) -> StepTimer {
+        StepTimer::start(step.into(), self.records.len(), &self.headers)
+    }
+
+    /// Records a step started with [`Pipeline::begin_step`] in the lineage.
+    fn end_step(&self, timer: StepTimer) {
+        timer.finish(&self.lineage, self.records.len(), &self.headers);
+    }
+
+    /// Columns kept by [`Pipeline::select_with_passthrough`] that were not selected explicitly.
+    pub fn passthrough(&self) -> &[String] {
+        &self.passthrough
+    }
+
     pub fn filter<F>(mut self, predicate: F) -> Self
     where
-        F: Fn(&Record) -> bool,
+        F: Fn(&Record) -> bool + Sync,
     {
-        let pb = ProgressBar::new(self.records.len() as u64);
-        pb.set_style(default_style());
-        pb.set_message("Filtering records...");
+        let step = self.begin_step("filter");
+        let pb = self
+            .progress
+            .start("Filtering records...", Some(self.records.len() as u64));
 
         let initial_count = self.records.len();
-        self.records.retain(|record| {
-            let keep = predicate(record);
-            pb.inc(1);
-            keep
-        });
+        let mut keep =
+            parallel::evaluate(&self.records, self.parallel, &*pb, predicate).into_iter();
+        self.records.retain(|_| keep.next().unwrap_or(false));
 
         let filtered_count = self.records.len();
-        pb.finish_with_message(format!(
+        pb.finish(&format!(
             "Filtered records: {} -> {}",
             initial_count, filtered_count
         ));
+        self.end_step(step);
         self
     }
 
-    pub fn from_file(mut self, path: &str) -> Result<Self, Box<dyn Error>> {
-        let file = File::open(path)?;
-        let mut rdr = ReaderBuilder::new().from_reader(file);
+    /// Loads a CSV file, a `.json`/`.ndjson` file, or an `.xlsx`/`.ods` workbook when
+    /// [`is_spreadsheet`] says so.
+    pub fn from_file(self, path: &str) -> Result<Self, Box<dyn Error>> {
+        let step = format!("from_file {}", path);
+        self.load(step, vec![path.to_string()])
+    }
 
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::with_template("{spinner:.green} {msg:.cyan.bold}").unwrap(),
-        );
-        pb.set_message(format!("Reading {}...", path));
-        pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    /// Loads and concatenates several files, each a path or a glob pattern such as
+    /// `users/ucmn/faculties/*.csv`. Every file must have the same columns as the first,
+    /// in any order; [`Pipeline::headers`] follow the first file's order.
+    pub fn from_files(self, patterns: &[&str]) -> Result<Self, Box<dyn Error>> {
+        let paths = expand_paths(patterns)?;
+        let step = format!("from_files {}", paths.join(", "));
+        self.load(step, paths)
+    }
 
-        let headers = rdr.headers()?.clone();
-        let mut records = Vec::new();
+    fn load(mut self, step: String, paths: Vec<String>) -> Result<Self, Box<dyn Error>> {
+        let step = self.begin_step(step);
+        let mut loaded: Option<(String, Vec<String>)> = None;
+        let mut all_records = Vec::new();
 
-        for result in rdr.records() {
-            let record = result?;
-            let mut map = HashMap::new();
-            for (header, value) in headers.iter().zip(record.iter()) {
-                map.insert(header.to_string(), value.to_string());
+        for path in &paths {
+            let (headers, records) = self.read(path)?;
+            match &loaded {
+                Some((first, expected)) => check_headers(first, expected, path, &headers)?,
+                None => loaded = Some((path.clone(), headers)),
             }
-            records.push(map);
-            pb.tick();
+            all_records.extend(records);
         }
 
-        pb.finish_with_message(format!("Loaded records from {}", path));
-        self.records = records;
+        self.records = all_records;
+        self.headers = loaded.map(|(_, headers)| headers).unwrap_or_default();
+        self.passthrough.clear();
+        self.end_step(step);
         Ok(self)
     }
 
+    /// Reads one file, adding the [`Pipeline::source_column`] if one is set.
+    fn read(&self, path: &str) -> Result<(Vec<String>, Vec<Record>), Box<dyn Error>> {
+        let pb = self.progress.start(&format!("Reading {}...", path), None);
+
+        let format = FileFormat::from_path(path);
+        let (mut headers, mut records) = if is_spreadsheet(path) {
+            spreadsheet::read_sheet(path, &self.sheet)?
+        } else if format != FileFormat::Csv {
+            format::read_json(path, format)?
+        } else {
+            let mut rdr = self.dialect.reader(path)?;
+            let headers = rdr.headers()?.clone();
+            let source: Arc<str> = Arc::from(path);
+            let mut records = Vec::new();
+
+            for result in rdr.records() {
+                let record = result?;
+                records.push(record_from_row(&headers, &record, &source));
+                pb.inc(1);
+            }
+            (headers.iter().map(str::to_string).collect(), records)
+        };
+
+        if let Some(column) = &self.source_column {
+            for record in &mut records {
+                record.insert(column.clone(), path.to_string());
+            }
+            if !headers.contains(column) {
+                headers.push(column.clone());
+            }
+        }
+
+        pb.finish(&format!("Loaded records from {}", path));
+        Ok((headers, records))
+    }
+
     pub fn derive<F>(mut self, header: &str, transform: F) -> Self
     where
-        F: Fn(&Record) -> String,
+        F: Fn(&Record) -> String + Sync,
     {
-        let pb = ProgressBar::new(self.records.len() as u64);
-        pb.set_style(default_style());
-        pb.set_message(format!("Deriving {}...", header));
+        let step = self.begin_step(format!("derive {}", header));
+        let pb = self.progress.start(
+            &format!("Deriving {}...", header),
+            Some(self.records.len() as u64),
+        );
 
-        for record in &mut self.records {
-            let new_value = transform(record);
+        let values = parallel::evaluate(&self.records, self.parallel, &*pb, transform);
+        for (record, new_value) in self.records.iter_mut().zip(values) {
             record.insert(header.to_string(), new_value);
-            pb.inc(1);
         }
 
-        pb.finish_with_message(format!("Finished deriving {}", header));
+        if !self.headers.iter().any(|h| h == header) {
+            self.headers.push(header.to_string());
+        }
+        pb.finish(&format!("Finished deriving {}", header));
+        self.end_step(step);
         self
     }
 
-    pub fn select(mut self, headers: Vec<&str>) -> Self {
-        let pb = ProgressBar::new(self.records.len() as u64);
-        pb.set_style(default_style());
-        pb.set_message("Selecting headers...");
+    /// Fallible [`Pipeline::derive`]. A failing row either aborts the step or, under
+    /// [`ErrorPolicy::Collect`], gets a blank value and an entry in the [`ErrorLog`].
+    pub fn try_derive<F>(self, header: &str, transform: F) -> Result<Self, Box<dyn Error>>
+    where
+        F: Fn(&Record) -> Result<String, String> + Sync,
+    {
+        let step = format!("try_derive {}", header);
+        self.derive_checked(step, header, transform)
+    }
 
-        for record in &mut self.records {
-            let mut new_record = HashMap::new();
-            for &header in &headers {
-                if let Some(value) = record.get(header) {
-                    new_record.insert(header.to_string(), value.clone());
-                } else {
-                    new_record.insert(header.to_string(), String::new());
+    /// Body of [`Pipeline::try_derive`], recorded in the lineage as `step`.
+    fn derive_checked<F>(
+        mut self,
+        step: String,
+        header: &str,
+        transform: F,
+    ) -> Result<Self, Box<dyn Error>>
+    where
+        F: Fn(&Record) -> Result<String, String> + Sync,
+    {
+        let step = self.begin_step(step);
+        let pb = self.progress.start(
+            &format!("Deriving {}...", header),
+            Some(self.records.len() as u64),
+        );
+
+        // Sequential runs evaluate row by row so an abort stops at the failing row.
+        let mut results = self
+            .parallel
+            .then(|| parallel::evaluate(&self.records, true, &*pb, &transform).into_iter());
+        let mut failed_count = 0usize;
+        for (idx, record) in self.records.iter_mut().enumerate() {
+            let result = match results.as_mut() {
+                Some(results) => results.next().expect("one result per record"),
+                None => {
+                    let result = transform(record);
+                    pb.inc(1);
+                    result
+                }
+            };
+            let new_value = match result {
+                Ok(value) => value,
+                Err(message) => {
+                    let error = RowError::new(record, idx, header, message);
+                    if self.policy == ErrorPolicy::Abort {
+                        pb.abandon(&format!("Failed deriving {}", header));
+                        return Err(error.into());
+                    }
+                    self.errors.push(error);
+                    failed_count += 1;
+                    String::new()
                 }
+            };
+            record.insert(header.to_string(), new_value);
+        }
+
+        if !self.headers.iter().any(|h| h == header) {
+            self.headers.push(header.to_string());
+        }
+        if failed_count > 0 {
+            pb.finish(&format!(
+                "Finished deriving {} ({} failed)",
+                header, failed_count
+            ));
+        } else {
+            pb.finish(&format!("Finished deriving {}", header));
+        }
+        self.end_step(step);
+        Ok(self)
+    }
+
+    pub fn select(self, headers: Vec<&str>) -> Self {
+        self.select_columns(headers, false)
+    }
+
+    /// Like [`Pipeline::select`], but every other column is kept after the selected ones,
+    /// in input order, and written by [`Pipeline::to_file`] without being required.
+    pub fn select_with_passthrough(self, headers: Vec<&str>) -> Self {
+        self.select_columns(headers, true)
+    }
+
+    fn select_columns(mut self, headers: Vec<&str>, passthrough: bool) -> Self {
+        let step = self.begin_step(if passthrough {
+            "select_with_passthrough"
+        } else {
+            "select"
+        });
+        let pb = self
+            .progress
+            .start("Selecting headers...", Some(self.records.len() as u64));
+
+        let rest = if passthrough {
+            passthrough_of(&self.headers, &headers)
+        } else {
+            Vec::new()
+        };
+
+        for record in &mut self.records {
+            let mut new_record = Record::with_capacity(headers.len() + rest.len());
+            new_record.set_position(record.position().cloned());
+            for &header in headers.iter() {
+                let value = record.remove(header).unwrap_or_default();
+                new_record.insert(header.to_string(), value);
+            }
+            for header in &rest {
+                let value = record.remove(header).unwrap_or_default();
+                new_record.insert(header.clone(), value);
             }
             *record = new_record;
             pb.inc(1);
         }
 
-        pb.finish_with_message("Finished header selection");
+        self.headers = headers.iter().map(|h| h.to_string()).collect();
+        self.headers.extend(rest.iter().cloned());
+        self.passthrough = rest;
+        pb.finish("Finished header selection");
+        self.end_step(step);
         self
     }
 
-    pub fn to_file(self, path: &str, headers: Vec<&str>) -> Result<(), Box<dyn Error>> {
-        if let Some(parent) = std::path::Path::new(path).parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        let file = File::create(path)?;
-        let mut wtr = WriterBuilder::new().from_writer(file);
-
-        let mut written_count = 0usize;
-        let mut skipped_count = 0usize;
-        for record in &self.records {
-            let mut complete = true;
-            for &header in &headers {
-                match record.get(header) {
-                    Some(value) if !value.trim().is_empty() => {}
-                    _ => {
-                        complete = false;
-                        break;
-                    }
-                }
-            }
-            if complete {
-                written_count += 1;
-            } else {
-                skipped_count += 1;
-            }
-        }
+    /// Hands the records over to a [`RecordStream`] so further steps run row by row.
+    pub fn into_stream(self) -> RecordStream<'static> {
+        RecordStream::from_parts(self.records, self.headers, self.passthrough)
+            .on_error(self.policy)
+            .with_error_log(self.errors)
+            .with_write_options(self.write_options)
+            .dialect(self.dialect)
+            .progress(self.progress)
+            .parallel(self.parallel)
+            .with_lineage(self.lineage)
+    }
 
-        let pb = ProgressBar::new(written_count as u64);
-        pb.set_style(default_style());
-        pb.set_message(format!("Writing to {}...", path));
+    /// Writes `headers` (plus any pass-through columns) to `path`. Rows where one of
+    /// `headers` is blank are skipped, sent to the rejects file, or fail the run in strict mode.
+    pub fn to_file(self, path: &str, headers: Vec<&str>) -> Result<(), Box<dyn Error>> {
+        let step = self.begin_step(format!("to_file {}", path));
+        let mut writer = OutputWriter::create(
+            path,
+            &headers,
+            &self.passthrough,
+            &self.write_options,
+            &self.dialect,
+            &self.errors,
+        )?;
 
-        wtr.write_record(&headers)?;
+        let pb = self.progress.start(
+            &format!("Writing to {}...", path),
+            Some(self.records.len() as u64),
+        );
 
-        for record in self.records {
-            let mut complete = true;
-            for &header in &headers {
-                match record.get(header) {
-                    Some(value) if !value.trim().is_empty() => {}
-                    _ => {
-                        complete = false;
-                        break;
-                    }
-                }
-            }
-            if !complete {
-                continue;
+        for (idx, record) in self.records.iter().enumerate() {
+            if let Err(err) = writer.write(record, idx) {
+                pb.abandon(&format!("Failed writing to {}", path));
+                return Err(err);
             }
-            let mut row = Vec::new();
-            for &header in &headers {
-                row.push(record.get(header).cloned().unwrap_or_default());
-            }
-            wtr.write_record(&row)?;
             pb.inc(1);
         }
 
-        wtr.flush()?;
-        if skipped_count > 0 {
-            pb.finish_with_message(format!(
-                "Finished writing to {} (written {}, skipped {} incomplete)",
-                path, written_count, skipped_count
-            ));
-        } else {
-            pb.finish_with_message(format!("Finished writing to {} (written {})", path, written_count));
-        }
+        pb.finish(&finish_message(
+            "writing",
+            path,
+            &writer,
+            &self.write_options,
+        ));
+        let written = writer.written;
+        writer.finish()?;
+        step.finish(
+            &self.lineage,
+            written,
+            &writer::output_row(&headers, &self.passthrough),
+        );
         Ok(())
     }
 }
@@ -0,0 +1,146 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What one step of a pipeline run did.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StepRecord {
+    pub step: String,
+    pub rows_in: usize,
+    pub rows_out: usize,
+    pub columns_added: Vec<String>,
+    pub columns_dropped: Vec<String>,
+    /// Time spent in the step. Streamed steps only count the time spent on their own rows.
+    pub duration_ms: f64,
+}
+
+impl fmt::Display for StepRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} rows",
+            self.step, self.rows_in, self.rows_out
+        )?;
+        if !self.columns_added.is_empty() {
+            write!(f, ", +[{}]", self.columns_added.join(", "))?;
+        }
+        if !self.columns_dropped.is_empty() {
+            write!(f, ", -[{}]", self.columns_dropped.join(", "))?;
+        }
+        write!(f, " ({:.1} ms)", self.duration_ms)
+    }
+}
+
+/// Shared, append-only audit trail of the steps a pipeline ran, in order.
+///
+/// Like [`crate::ErrorLog`], clones share the same list, so a handle taken before `to_file`
+/// still sees the write step and can be dumped with [`Lineage::write_json`] afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct Lineage {
+    steps: Arc<Mutex<Vec<StepRecord>>>,
+}
+
+impl Lineage {
+    /// Appends `step` and returns its index for later [`Lineage::update`]s.
+    pub(crate) fn push(&self, step: StepRecord) -> usize {
+        let mut steps = self.steps.lock().unwrap();
+        steps.push(step);
+        steps.len() - 1
+    }
+
+    pub(crate) fn update<F>(&self, index: usize, update: F)
+    where
+        F: FnOnce(&mut StepRecord),
+    {
+        if let Some(step) = self.steps.lock().unwrap().get_mut(index) {
+            update(step);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn steps(&self) -> Vec<StepRecord> {
+        self.steps.lock().unwrap().clone()
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(&*self.steps.lock().unwrap())?)
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_json()? + "\n")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Lineage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps = self.steps.lock().unwrap();
+        write!(f, "Lineage ({} step(s)):", steps.len())?;
+        for (idx, step) in steps.iter().enumerate() {
+            write!(f, "\n{:>3}. {}", idx + 1, step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Columns of `after` missing from `before`, and columns of `before` missing from `after`.
+pub(crate) fn column_changes(before: &[String], after: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = after
+        .iter()
+        .filter(|header| !before.contains(header))
+        .cloned()
+        .collect();
+    let dropped = before
+        .iter()
+        .filter(|header| !after.contains(header))
+        .cloned()
+        .collect();
+    (added, dropped)
+}
+
+/// An in-memory step being timed; see `Pipeline::begin_step`.
+pub(crate) struct StepTimer {
+    step: String,
+    rows_in: usize,
+    headers: Vec<String>,
+    started: Instant,
+}
+
+impl StepTimer {
+    pub fn start(step: String, rows_in: usize, headers: &[String]) -> Self {
+        Self {
+            step,
+            rows_in,
+            headers: headers.to_vec(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn finish(self, lineage: &Lineage, rows_out: usize, headers: &[String]) {
+        let (columns_added, columns_dropped) = column_changes(&self.headers, headers);
+        lineage.push(StepRecord {
+            step: self.step,
+            rows_in: self.rows_in,
+            rows_out,
+            columns_added,
+            columns_dropped,
+            duration_ms: millis(self.started.elapsed()),
+        });
+    }
+}
+
+pub(crate) fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
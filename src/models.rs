@@ -0,0 +1,170 @@
+use crate::cli::Cli;
+use crate::constants::*;
+use crate::utils::{
+    generate_fake_user, get_category_path, get_course_end_date, get_course_start_date,
+    get_short_name,
+};
+use serde::{Deserialize, Serialize};
+
+/// A row of a parsed curriculum CSV, the input of `course` mode.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CurriculumRow {
+    #[serde(rename = "Program")]
+    pub program: String,
+    #[serde(rename = "Year")]
+    pub year: String,
+    #[serde(rename = "Semester")]
+    pub semester: u8,
+    #[serde(rename = "Course Code")]
+    pub course_code: String,
+    #[serde(rename = "Descriptive Title")]
+    pub descriptive_title: String,
+}
+
+/// A row of a Moodle course upload file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MoodleCourse {
+    pub shortname: String,
+    pub fullname: String,
+    pub category_path: String,
+    pub startdate: String,
+    pub enddate: String,
+    pub visible: u8,
+    pub year: String,
+}
+
+impl MoodleCourse {
+    pub const HEADERS: [&str; 7] = [
+        HEADER_SHORTNAME,
+        HEADER_FULLNAME,
+        HEADER_CATEGORY_PATH,
+        HEADER_STARTDATE,
+        HEADER_ENDDATE,
+        HEADER_VISIBLE,
+        HEADER_YEAR,
+    ];
+
+    pub fn from_curriculum(row: CurriculumRow, term: &Term) -> Result<Self, String> {
+        let semester = row.semester.to_string();
+        Ok(Self {
+            shortname: term.shortname(&row.course_code, &semester),
+            category_path: term.category_path(&row.program, &semester)?,
+            startdate: term.start_date(&semester)?,
+            enddate: term.end_date(&semester)?,
+            fullname: row.descriptive_title,
+            visible: 1,
+            year: row.year,
+        })
+    }
+}
+
+/// A row of a Moodle user upload file.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MoodleUser {
+    pub username: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub email: String,
+    pub password: String,
+}
+
+impl MoodleUser {
+    pub const HEADERS: [&str; 5] = [
+        HEADER_USERNAME,
+        HEADER_FIRSTNAME,
+        HEADER_LASTNAME,
+        HEADER_EMAIL,
+        HEADER_PASSWORD,
+    ];
+
+    /// A user with a fake name and email and the default password.
+    pub fn fake(username: String) -> Self {
+        let (firstname, lastname, email) = generate_fake_user();
+        Self {
+            username,
+            firstname,
+            lastname,
+            email,
+            password: DEFAULT_PASSWORD.to_string(),
+        }
+    }
+}
+
+/// A user enrolled in one course, as in the long layout of `enrol-longer`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Enrolment {
+    pub username: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub email: String,
+    pub password: String,
+    pub course: String,
+    pub role: String,
+}
+
+impl Enrolment {
+    pub fn new(user: &MoodleUser, course: &str, role: &str) -> Self {
+        Self {
+            username: user.username.clone(),
+            firstname: user.firstname.clone(),
+            lastname: user.lastname.clone(),
+            email: user.email.clone(),
+            password: user.password.clone(),
+            course: course.to_string(),
+            role: role.to_string(),
+        }
+    }
+}
+
+/// Campus, department and academic year that course names, categories and dates use.
+#[derive(Clone, Debug)]
+pub struct Term {
+    campus_upper: String,
+    dept_upper: String,
+    start_year_full: String,
+    end_year_full: String,
+    start_year_short: String,
+    end_year_short: String,
+}
+
+impl Term {
+    pub fn from_cli(cli: &Cli) -> Self {
+        Self {
+            campus_upper: cli.campus.to_string().to_uppercase(),
+            dept_upper: cli.dept.as_deref().unwrap_or_default().to_uppercase(),
+            start_year_full: cli.start_date[0..4].to_string(),
+            end_year_full: cli.end_date[0..4].to_string(),
+            start_year_short: cli.start_date[2..4].to_string(),
+            end_year_short: cli.end_date[2..4].to_string(),
+        }
+    }
+
+    pub fn shortname(&self, course_code: &str, semester: &str) -> String {
+        get_short_name(
+            course_code,
+            semester,
+            &self.campus_upper,
+            &self.start_year_short,
+            &self.end_year_short,
+        )
+    }
+
+    pub fn category_path(&self, program: &str, semester: &str) -> Result<String, String> {
+        get_category_path(
+            program,
+            semester,
+            &self.campus_upper,
+            &self.dept_upper,
+            &self.start_year_short,
+            &self.end_year_short,
+        )
+    }
+
+    pub fn start_date(&self, semester: &str) -> Result<String, String> {
+        get_course_start_date(semester, &self.start_year_full, &self.end_year_full)
+    }
+
+    pub fn end_date(&self, semester: &str) -> Result<String, String> {
+        get_course_end_date(semester, &self.start_year_full, &self.end_year_full)
+    }
+}
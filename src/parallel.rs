@@ -0,0 +1,75 @@
+use crate::{Record, StepProgress};
+use rayon::prelude::*;
+use std::error::Error;
+
+/// Rows buffered by a parallel [`crate::RecordStream`] step before they are evaluated.
+pub(crate) const CHUNK_SIZE: usize = 4096;
+
+/// Evaluates `f` on every record, on the rayon pool when `parallel` is set.
+/// Results are in record order either way.
+pub(crate) fn evaluate<T, F>(
+    records: &[Record],
+    parallel: bool,
+    pb: &dyn StepProgress,
+    f: F,
+) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Record) -> T + Sync,
+{
+    let step = |record: &Record| {
+        let value = f(record);
+        pb.inc(1);
+        value
+    };
+    if parallel {
+        records.par_iter().map(step).collect()
+    } else {
+        records.iter().map(step).collect()
+    }
+}
+
+/// Groups indexed stream rows into chunks of up to `size` records. A read error ends the
+/// current chunk and is yielded on its own, so rows before it are not lost.
+pub(crate) struct Chunks<I> {
+    rows: I,
+    size: usize,
+    pending: Option<Box<dyn Error>>,
+}
+
+impl<I> Chunks<I> {
+    pub fn new(rows: I, size: usize) -> Self {
+        Self {
+            rows,
+            size,
+            pending: None,
+        }
+    }
+}
+
+impl<I> Iterator for Chunks<I>
+where
+    I: Iterator<Item = (usize, Result<Record, Box<dyn Error>>)>,
+{
+    type Item = Result<Vec<(usize, Record)>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.pending.take() {
+            return Some(Err(err));
+        }
+
+        let mut chunk = Vec::with_capacity(self.size);
+        while chunk.len() < self.size {
+            match self.rows.next() {
+                Some((idx, Ok(record))) => chunk.push((idx, record)),
+                Some((_, Err(err))) if chunk.is_empty() => return Some(Err(err)),
+                Some((_, Err(err))) => {
+                    self.pending = Some(err);
+                    break;
+                }
+                None => break,
+            }
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }
+}
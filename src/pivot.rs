@@ -0,0 +1,182 @@
+use crate::{Pipeline, Record};
+use indexmap::IndexMap;
+
+/// Numbers `n` for which `headers` contains `{stem}{n}` for at least one stem, ascending.
+fn family_numbers(headers: &[String], stems: &[&str]) -> Vec<u32> {
+    let mut numbers: Vec<u32> = headers
+        .iter()
+        .filter_map(|header| {
+            stems.iter().find_map(|stem| {
+                let suffix = header.strip_prefix(stem)?;
+                if suffix.is_empty() || !suffix.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                suffix.parse().ok()
+            })
+        })
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+fn is_family_member(header: &str, stems: &[&str]) -> bool {
+    stems.iter().any(|stem| {
+        header
+            .strip_prefix(stem)
+            .is_some_and(|suffix| !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+impl Pipeline {
+    /// Turns numbered column families such as `course1, role1, course2, role2` into one row
+    /// per number with plain `course, role` columns. Every other column is repeated.
+    ///
+    /// Numbers where all stems are blank are dropped; a row with no entries at all is kept
+    /// once with blank stems so [`Pipeline::pivot_wider`] can restore it.
+    pub fn pivot_longer(mut self, stems: Vec<&str>) -> Self {
+        let step = self.begin_step(format!("pivot_longer {}", stems.join(", ")));
+        let pb = self.progress.start(
+            &format!("Pivoting {} longer...", stems.join(", ")),
+            Some(self.records.len() as u64),
+        );
+
+        let numbers = family_numbers(&self.headers, &stems);
+        let ids: Vec<String> = self
+            .headers
+            .iter()
+            .filter(|header| !is_family_member(header, &stems))
+            .cloned()
+            .collect();
+
+        let initial_count = self.records.len();
+        let mut records = Vec::new();
+        for record in &self.records {
+            let mut base = Record::with_capacity(ids.len() + stems.len());
+            base.set_position(record.position().cloned());
+            for id in &ids {
+                base.insert(id.clone(), record.get(id).cloned().unwrap_or_default());
+            }
+
+            let mut emitted = false;
+            for number in &numbers {
+                let values: Vec<String> = stems
+                    .iter()
+                    .map(|stem| {
+                        record
+                            .get(&format!("{}{}", stem, number))
+                            .cloned()
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                if values.iter().all(|value| value.trim().is_empty()) {
+                    continue;
+                }
+                let mut new_record = base.clone();
+                for (stem, value) in stems.iter().zip(values) {
+                    new_record.insert(stem.to_string(), value);
+                }
+                records.push(new_record);
+                emitted = true;
+            }
+
+            if !emitted {
+                for stem in &stems {
+                    base.insert(stem.to_string(), String::new());
+                }
+                records.push(base);
+            }
+            pb.inc(1);
+        }
+
+        self.records = records;
+        self.headers = ids;
+        self.headers
+            .extend(stems.iter().map(|stem| stem.to_string()));
+        self.passthrough.clear();
+
+        pb.finish(&format!(
+            "Pivoted records: {} -> {}",
+            initial_count,
+            self.records.len()
+        ));
+        self.end_step(step);
+        self
+    }
+
+    /// Inverse of [`Pipeline::pivot_longer`]: rows that agree on every column except `stems`
+    /// are merged into one row with `{stem}1, {stem}2, ...` columns, numbered in row order.
+    pub fn pivot_wider(mut self, stems: Vec<&str>) -> Self {
+        let step = self.begin_step(format!("pivot_wider {}", stems.join(", ")));
+        let pb = self.progress.start(
+            &format!("Pivoting {} wider...", stems.join(", ")),
+            Some(self.records.len() as u64),
+        );
+
+        let ids: Vec<String> = self
+            .headers
+            .iter()
+            .filter(|header| !stems.contains(&header.as_str()))
+            .cloned()
+            .collect();
+
+        let initial_count = self.records.len();
+        let mut groups: IndexMap<Vec<String>, (Record, Vec<Vec<String>>)> = IndexMap::new();
+        for record in &self.records {
+            let key: Vec<String> = ids
+                .iter()
+                .map(|id| record.get(id).cloned().unwrap_or_default())
+                .collect();
+            let (_, entries) = groups.entry(key).or_insert_with_key(|key| {
+                let mut base: Record = ids.iter().cloned().zip(key.iter().cloned()).collect();
+                base.set_position(record.position().cloned());
+                (base, Vec::new())
+            });
+
+            let values: Vec<String> = stems
+                .iter()
+                .map(|&stem| record.get(stem).cloned().unwrap_or_default())
+                .collect();
+            if values.iter().any(|value| !value.trim().is_empty()) {
+                entries.push(values);
+            }
+            pb.inc(1);
+        }
+
+        let width = groups
+            .values()
+            .map(|(_, entries)| entries.len())
+            .max()
+            .unwrap_or(0);
+        let mut headers = ids;
+        for number in 1..=width {
+            for stem in &stems {
+                headers.push(format!("{}{}", stem, number));
+            }
+        }
+
+        self.records = groups
+            .into_values()
+            .map(|(mut record, entries)| {
+                for number in 1..=width {
+                    let values = entries.get(number - 1);
+                    for (idx, stem) in stems.iter().enumerate() {
+                        let value = values.map(|v| v[idx].clone()).unwrap_or_default();
+                        record.insert(format!("{}{}", stem, number), value);
+                    }
+                }
+                record
+            })
+            .collect();
+        self.headers = headers;
+        self.passthrough.clear();
+
+        pb.finish(&format!(
+            "Pivoted records: {} -> {}",
+            initial_count,
+            self.records.len()
+        ));
+        self.end_step(step);
+        self
+    }
+}
@@ -0,0 +1,164 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::json;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Receives progress from every `Pipeline` and `RecordStream` step.
+pub trait ProgressSink: Send + Sync {
+    /// Starts a step over `total` rows, or a step of unknown length when `None`.
+    fn start(&self, message: &str, total: Option<u64>) -> Box<dyn StepProgress>;
+}
+
+/// Handle to one running step.
+pub trait StepProgress: Send + Sync {
+    fn inc(&self, delta: u64);
+    fn finish(&self, message: &str);
+    /// Ends the step early because it failed.
+    fn abandon(&self, message: &str);
+}
+
+fn default_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "{msg:>30.cyan.bold} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})",
+    )
+    .unwrap()
+    .progress_chars("=>-")
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner:.green} {msg:.cyan.bold}").unwrap()
+}
+
+/// Interactive `indicatif` bars on stderr; the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BarProgress;
+
+impl ProgressSink for BarProgress {
+    fn start(&self, message: &str, total: Option<u64>) -> Box<dyn StepProgress> {
+        let pb = match total {
+            Some(total) => {
+                let pb = ProgressBar::new(total);
+                pb.set_style(default_style());
+                pb
+            }
+            None => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(spinner_style());
+                pb.enable_steady_tick(Duration::from_millis(100));
+                pb
+            }
+        };
+        pb.set_message(message.to_string());
+        Box::new(pb)
+    }
+}
+
+impl StepProgress for ProgressBar {
+    fn inc(&self, delta: u64) {
+        ProgressBar::inc(self, delta);
+    }
+
+    fn finish(&self, message: &str) {
+        self.finish_with_message(message.to_string());
+    }
+
+    fn abandon(&self, message: &str) {
+        self.abandon_with_message(message.to_string());
+    }
+}
+
+/// Reports nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SilentProgress;
+
+struct SilentStep;
+
+impl ProgressSink for SilentProgress {
+    fn start(&self, _message: &str, _total: Option<u64>) -> Box<dyn StepProgress> {
+        Box::new(SilentStep)
+    }
+}
+
+impl StepProgress for SilentStep {
+    fn inc(&self, _delta: u64) {}
+    fn finish(&self, _message: &str) {}
+    fn abandon(&self, _message: &str) {}
+}
+
+/// One JSON object per line on stderr for `start`, `progress`, `finish` and `abandon` events.
+///
+/// Steps are numbered in start order across the process, so pipelines created one after
+/// another never reuse a number. `progress` is emitted at every tenth of a known total.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonProgress;
+
+static NEXT_STEP: AtomicU64 = AtomicU64::new(1);
+
+struct JsonStep {
+    step: u64,
+    total: Option<u64>,
+    position: AtomicU64,
+    reported: Mutex<u64>,
+    started: Instant,
+}
+
+fn emit(event: serde_json::Value) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "{}", event);
+}
+
+impl ProgressSink for JsonProgress {
+    fn start(&self, message: &str, total: Option<u64>) -> Box<dyn StepProgress> {
+        let step = NEXT_STEP.fetch_add(1, Ordering::Relaxed);
+        emit(json!({ "event": "start", "step": step, "message": message, "total": total }));
+        Box::new(JsonStep {
+            step,
+            total,
+            position: AtomicU64::new(0),
+            reported: Mutex::new(0),
+            started: Instant::now(),
+        })
+    }
+}
+
+impl JsonStep {
+    fn end(&self, event: &str, message: &str) {
+        emit(json!({
+            "event": event,
+            "step": self.step,
+            "message": message,
+            "position": self.position.load(Ordering::Relaxed),
+            "elapsed_ms": self.started.elapsed().as_millis() as u64,
+        }));
+    }
+}
+
+impl StepProgress for JsonStep {
+    fn inc(&self, delta: u64) {
+        let position = self.position.fetch_add(delta, Ordering::Relaxed) + delta;
+        let Some(total) = self.total.filter(|&total| total > 0) else {
+            return;
+        };
+        let tenth = position * 10 / total;
+        let mut reported = self.reported.lock().unwrap();
+        if tenth > *reported {
+            *reported = tenth;
+            emit(json!({
+                "event": "progress",
+                "step": self.step,
+                "position": position,
+                "total": total,
+            }));
+        }
+    }
+
+    fn finish(&self, message: &str) {
+        self.end("finish", message);
+    }
+
+    fn abandon(&self, message: &str) {
+        self.end("abandon", message);
+    }
+}
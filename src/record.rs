@@ -0,0 +1,120 @@
+use indexmap::IndexMap;
+use indexmap::map::{IntoIter, Iter};
+use std::sync::Arc;
+
+/// Where a record was read from: the input path and the 1-based line it started on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub path: Arc<str>,
+    pub line: u64,
+}
+
+/// A single CSV row whose columns keep the order they were read or inserted in.
+///
+/// Equality only looks at the columns, not at the [`Position`] the row was read from.
+#[derive(Clone, Debug, Default)]
+pub struct Record {
+    fields: IndexMap<String, String>,
+    position: Option<Position>,
+}
+
+impl PartialEq for Record {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields
+    }
+}
+
+impl Eq for Record {}
+
+impl Record {
+    pub fn new() -> Self {
+        Self {
+            fields: IndexMap::new(),
+            position: None,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            fields: IndexMap::with_capacity(capacity),
+            position: None,
+        }
+    }
+
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_ref()
+    }
+
+    pub fn set_position(&mut self, position: Option<Position>) {
+        self.position = position;
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, header: &str) -> Option<&String> {
+        self.fields.get(header)
+    }
+
+    pub fn get_mut(&mut self, header: &str) -> Option<&mut String> {
+        self.fields.get_mut(header)
+    }
+
+    pub fn contains_key(&self, header: &str) -> bool {
+        self.fields.contains_key(header)
+    }
+
+    /// Sets `header` to `value`. An existing column keeps its position; a new one is appended.
+    pub fn insert(&mut self, header: String, value: String) -> Option<String> {
+        self.fields.insert(header, value)
+    }
+
+    /// Removes `header` while keeping the order of the remaining columns.
+    pub fn remove(&mut self, header: &str) -> Option<String> {
+        self.fields.shift_remove(header)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.fields.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.fields.values()
+    }
+
+    pub fn iter(&self) -> Iter<'_, String, String> {
+        self.fields.iter()
+    }
+}
+
+impl FromIterator<(String, String)> for Record {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self {
+            fields: iter.into_iter().collect(),
+            position: None,
+        }
+    }
+}
+
+impl IntoIterator for Record {
+    type Item = (String, String);
+    type IntoIter = IntoIter<String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Record {
+    type Item = (&'a String, &'a String);
+    type IntoIter = Iter<'a, String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter()
+    }
+}
@@ -0,0 +1,824 @@
+use crate::cli::{Cli, CommandMode};
+use crate::constants::*;
+use crate::dry_run::{
+    diff_rows, print_diff, print_preview_table, report_errors, report_lineage, report_missing,
+    report_validation,
+};
+use crate::models::{CurriculumRow, Enrolment, MoodleCourse, MoodleUser, Term};
+use crate::spec::{ColumnSpec, InputSpec, Spec, Step, Vars, expand_columns, schema};
+use crate::utils::{generate_faculty_username, generate_username};
+use moodle_course_builder::{
+    CastType, DedupeStrategy, ErrorLog, ErrorPolicy, Lineage, Pipeline, Record, RecordStream,
+    Schema, SortKey, Template, ValidationReport, natural_cmp,
+};
+use std::error::Error;
+
+/// A built-in derive function usable from a spec.
+type RowFn = Box<dyn Fn(&Record) -> Result<String, String> + Send + Sync>;
+
+/// Rows between steps: streamed until a step needs all of them at once.
+enum Rows {
+    Stream(RecordStream<'static>),
+    Table(Pipeline),
+}
+
+impl Rows {
+    fn into_pipeline(self) -> Result<Pipeline, Box<dyn Error>> {
+        match self {
+            Rows::Stream(stream) => stream.collect(),
+            Rows::Table(pipeline) => Ok(pipeline),
+        }
+    }
+
+    fn headers(&self) -> &[String] {
+        match self {
+            Rows::Stream(stream) => stream.headers(),
+            Rows::Table(pipeline) => pipeline.headers(),
+        }
+    }
+
+    fn passthrough(&self) -> &[String] {
+        match self {
+            Rows::Stream(stream) => stream.passthrough(),
+            Rows::Table(pipeline) => pipeline.passthrough(),
+        }
+    }
+
+    fn error_log(&self) -> ErrorLog {
+        match self {
+            Rows::Stream(stream) => stream.error_log(),
+            Rows::Table(pipeline) => pipeline.error_log(),
+        }
+    }
+
+    fn lineage(&self) -> Lineage {
+        match self {
+            Rows::Stream(stream) => stream.lineage(),
+            Rows::Table(pipeline) => pipeline.lineage(),
+        }
+    }
+
+    fn filter<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&Record) -> bool + Send + Sync + 'static,
+    {
+        match self {
+            Rows::Stream(stream) => Rows::Stream(stream.filter(predicate)),
+            Rows::Table(pipeline) => Rows::Table(pipeline.filter(predicate)),
+        }
+    }
+
+    fn derive<F>(self, header: &str, transform: F) -> Self
+    where
+        F: Fn(&Record) -> String + Send + Sync + 'static,
+    {
+        match self {
+            Rows::Stream(stream) => Rows::Stream(stream.derive(header, transform)),
+            Rows::Table(pipeline) => Rows::Table(pipeline.derive(header, transform)),
+        }
+    }
+
+    fn try_derive(self, header: &str, transform: RowFn) -> Result<Self, Box<dyn Error>> {
+        Ok(match self {
+            Rows::Stream(stream) => Rows::Stream(stream.try_derive(header, transform)),
+            Rows::Table(pipeline) => Rows::Table(pipeline.try_derive(header, transform)?),
+        })
+    }
+
+    /// Checks rows against `schema`; with a stream the report fills in as it is written.
+    fn validate(self, schema: &Schema) -> Result<(Self, ValidationReport), Box<dyn Error>> {
+        Ok(match self {
+            Rows::Stream(stream) => {
+                let (stream, report) = stream.validate(schema);
+                (Rows::Stream(stream), report)
+            }
+            Rows::Table(pipeline) => {
+                let report = pipeline.validate(schema)?;
+                (Rows::Table(pipeline), report)
+            }
+        })
+    }
+
+    fn select(self, headers: Vec<&str>, passthrough: bool) -> Self {
+        match (self, passthrough) {
+            (Rows::Stream(stream), false) => Rows::Stream(stream.select(headers)),
+            (Rows::Stream(stream), true) => Rows::Stream(stream.select_with_passthrough(headers)),
+            (Rows::Table(pipeline), false) => Rows::Table(pipeline.select(headers)),
+            (Rows::Table(pipeline), true) => Rows::Table(pipeline.select_with_passthrough(headers)),
+        }
+    }
+}
+
+/// Runs `spec` with the options and variables from the command line.
+pub fn run_spec(cli: Cli, spec: Spec) -> Result<(), Box<dyn Error>> {
+    let vars = Vars::from_cli(&cli)?;
+    println!("Running spec '{}'", spec.name);
+    if !spec.description.is_empty() {
+        println!("{}", spec.description);
+    }
+
+    let mut rows = load(&cli, &vars, &spec.input, error_policy(&cli))?;
+    let mut reports = Vec::new();
+    rows = validate(
+        rows,
+        &format!("{} input", spec.name),
+        &spec.input.schema,
+        &mut reports,
+    )?;
+    // --derive columns are added before the first select, while input columns are still there.
+    let derives = cli.derives()?;
+    // Split and sample columns a select kept without the spec asking for them.
+    let mut kept = Vec::new();
+    let mut derived = false;
+    for step in &spec.steps {
+        if step.when.as_deref().is_some_and(|name| !vars.is_set(name))
+            || step.unless.as_deref().is_some_and(|name| vars.is_set(name))
+        {
+            continue;
+        }
+        if !derived && matches!(step.step, Step::Select { .. }) {
+            rows = apply_derives(rows, &derives, &vars)?;
+            derived = true;
+        }
+        rows = apply(rows, &step.step, &cli, &vars, &mut kept)?;
+    }
+    if !derived {
+        rows = apply_derives(rows, &derives, &vars)?;
+    }
+    rows = validate(
+        rows,
+        &format!("{} output", spec.name),
+        &spec.output.schema,
+        &mut reports,
+    )?;
+
+    let out_path = vars.expand(&spec.output.path)?;
+    let output_headers = if spec.output.headers.is_empty() {
+        rows.headers()
+            .iter()
+            .filter(|header| !rows.passthrough().contains(header) && !kept.contains(header))
+            .cloned()
+            .collect()
+    } else {
+        let count = match spec.output.for_each.as_deref() {
+            Some(name) => vars.list(name)?.len(),
+            None => 0,
+        };
+        let mut headers = expand_columns(&vars.expand_all(&spec.output.headers)?, count);
+        for (name, _) in &derives {
+            if !headers.iter().any(|header| header == name) {
+                headers.push(name.to_string());
+            }
+        }
+        headers
+    };
+    let output_headers: Vec<&str> = output_headers.iter().map(String::as_str).collect();
+    let output = Output {
+        path: &out_path,
+        headers: output_headers,
+        report_missing: spec.output.report_missing,
+    };
+    if finish(rows, &cli, output, &reports)? {
+        return Ok(());
+    }
+
+    println!("Spec '{}' complete.", spec.name);
+    Ok(())
+}
+
+/// Where and what a run writes.
+struct Output<'a> {
+    path: &'a str,
+    headers: Vec<&'a str>,
+    /// Summarize blank output values in dry runs.
+    report_missing: bool,
+}
+
+/// `--fail-fast` as an [`ErrorPolicy`].
+fn error_policy(cli: &Cli) -> ErrorPolicy {
+    if cli.fail_fast {
+        ErrorPolicy::Abort
+    } else {
+        ErrorPolicy::Collect
+    }
+}
+
+/// Ends a run: writes the `--sample`, then previews the rows in a dry run or writes them,
+/// and reports lineage, row errors and `reports`. Returns whether it was a dry run.
+fn finish(
+    mut rows: Rows,
+    cli: &Cli,
+    output: Output,
+    reports: &[ValidationReport],
+) -> Result<bool, Box<dyn Error>> {
+    if let Some(size) = cli.sample {
+        let pipeline = rows.into_pipeline()?;
+        write_sample(&pipeline, cli, size, &output.headers)?;
+        rows = Rows::Table(pipeline);
+    }
+    let errors = rows.error_log();
+    let lineage = rows.lineage();
+
+    if cli.dry_run {
+        let pipeline = rows.into_pipeline()?;
+        println!(
+            "Dry run: would write {} records to {}",
+            pipeline.len(),
+            output.path
+        );
+        if let Some(limit) = cli.dry_run_show {
+            println!("Previewing first {} records:", limit);
+            let preview_headers: Vec<&str> =
+                pipeline.headers().iter().map(|h| h.as_str()).collect();
+            print_preview_table(pipeline.records(), &preview_headers, limit);
+        }
+        if output.report_missing {
+            report_missing(pipeline.records(), &output.headers);
+        }
+        report_lineage(&lineage);
+        report_errors(&errors);
+        check_validation(reports)?;
+        return Ok(true);
+    }
+
+    match rows {
+        Rows::Stream(stream) if cli.split_by.is_empty() => {
+            let mut stream = stream
+                .strict(cli.strict)
+                .dialect(cli.output_dialect()?)
+                .format(cli.output_format());
+            if let Some(rejects_path) = cli.rejects_path.as_deref() {
+                stream = stream.rejects_to(rejects_path);
+            }
+            stream.to_file(output.path, output.headers)?;
+        }
+        rows => {
+            let mut pipeline = rows
+                .into_pipeline()?
+                .strict(cli.strict)
+                .dialect(cli.output_dialect()?)
+                .format(cli.output_format());
+            if let Some(rejects_path) = cli.rejects_path.as_deref() {
+                pipeline = pipeline.rejects_to(rejects_path);
+            }
+            write_output(pipeline, cli, output.path, output.headers)?;
+        }
+    }
+    write_lineage(cli, &lineage)?;
+    report_errors(&errors);
+    check_validation(reports)?;
+    Ok(false)
+}
+
+/// Prints the schema reports and fails the run if they found any problem, so CI notices.
+fn check_validation(reports: &[ValidationReport]) -> Result<(), Box<dyn Error>> {
+    report_validation(reports);
+    let problems: usize = reports.iter().map(|report| report.problems().len()).sum();
+    if problems > 0 {
+        return Err(format!("schema validation found {} row problem(s)", problems).into());
+    }
+    Ok(())
+}
+
+/// Runs a course, seed or enrol mode on typed rows instead of its spec. The input is read
+/// into [`CurriculumRow`] or [`MoodleUser`], so a missing column or a value of the wrong
+/// type fails its row as it is read rather than leaving blanks in the output.
+pub fn run_typed(cli: Cli) -> Result<(), Box<dyn Error>> {
+    if cli.dedupe.is_some()
+        || cli.passthrough
+        || !cli.derive.is_empty()
+        || cli.source_column.is_some()
+        || cli.parallel
+    {
+        return Err(
+            "--typed does not support --dedupe, --passthrough, --derive, --source-column or --parallel"
+                .into(),
+        );
+    }
+    let base = Pipeline::new()
+        .progress(cli.progress_sink())
+        .on_error(error_policy(&cli));
+
+    let (pipeline, headers) = match cli.mode {
+        CommandMode::Course => {
+            let term = Term::from_cli(&cli);
+            let mut rows = read_inputs(base, &cli)?.typed::<CurriculumRow>()?;
+            if cli.sort {
+                rows = rows.sort_by(|a, b| {
+                    a.program
+                        .cmp(&b.program)
+                        .then_with(|| natural_cmp(&a.year, &b.year))
+                        .then(a.semester.cmp(&b.semester))
+                        .then_with(|| natural_cmp(&a.course_code, &b.course_code))
+                });
+            }
+            let courses = rows.try_map(|row| MoodleCourse::from_curriculum(row, &term))?;
+            (courses.into_pipeline()?, MoodleCourse::HEADERS.to_vec())
+        }
+        CommandMode::UserSeed | CommandMode::FacultySeed => {
+            let kind = if cli.mode == CommandMode::UserSeed {
+                "students"
+            } else {
+                "faculty"
+            };
+            let mut users =
+                base.with_rows(generate_users(kind, &cli.campus.to_string(), cli.count)?);
+            if cli.sort {
+                users = users.sort_by(|a, b| natural_cmp(&a.username, &b.username));
+            }
+            (users.into_pipeline()?, MoodleUser::HEADERS.to_vec())
+        }
+        CommandMode::UserEnrol | CommandMode::FacultyEnrol => {
+            if cli.courses.is_empty() {
+                return Err("--typed enrol modes need --courses".into());
+            }
+            let role = if cli.mode == CommandMode::UserEnrol {
+                ROLE_STUDENT
+            } else {
+                ROLE_EDITINGTEACHER
+            };
+            let mut users = read_inputs(base, &cli)?.typed::<MoodleUser>()?;
+            if cli.sort {
+                users = users.sort_by(|a, b| natural_cmp(&a.username, &b.username));
+            }
+            let enrolments = users.flat_map(|user| {
+                cli.courses
+                    .iter()
+                    .map(|course| Enrolment::new(&user, course, role))
+                    .collect::<Vec<_>>()
+            });
+            let pipeline = enrolments
+                .into_pipeline()?
+                .pivot_wider(vec![HEADER_COURSE, HEADER_ROLE]);
+            (pipeline, MoodleUser::HEADERS.to_vec())
+        }
+        mode => return Err(format!("--typed does not support {:?} mode", mode).into()),
+    };
+
+    let mut headers: Vec<String> = headers.into_iter().map(String::from).collect();
+    if matches!(cli.mode, CommandMode::UserEnrol | CommandMode::FacultyEnrol) {
+        let stems = [
+            format!("{}{{n}}", HEADER_COURSE),
+            format!("{}{{n}}", HEADER_ROLE),
+        ];
+        headers.extend(expand_columns(&stems, cli.courses.len()));
+    }
+    let output = Output {
+        path: &cli.out_path,
+        headers: headers.iter().map(String::as_str).collect(),
+        report_missing: false,
+    };
+    if finish(Rows::Table(pipeline), &cli, output, &[])? {
+        return Ok(());
+    }
+
+    println!("Typed {:?} run complete.", cli.mode);
+    Ok(())
+}
+
+/// Loads every `--in-path` into `pipeline`.
+fn read_inputs(pipeline: Pipeline, cli: &Cli) -> Result<Pipeline, Box<dyn Error>> {
+    if cli.in_path.is_empty() {
+        return Err("cli --in-path not defined".into());
+    }
+    let paths: Vec<&str> = cli.in_path.iter().map(String::as_str).collect();
+    pipeline
+        .dialect(cli.input_dialect()?)
+        .sheet(cli.sheet_options())
+        .from_files(&paths)
+}
+
+/// Compares the two `--in-path` files by `--keys`, prints what changed and, unless this
+/// is a dry run, writes one row per added, removed or changed field to `--out-path`.
+pub fn run_diff(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let [old_path, new_path] = cli.in_path.as_slice() else {
+        return Err("diff mode expects --in-path OLD NEW".into());
+    };
+    let load = |path: &str| -> Result<Pipeline, Box<dyn Error>> {
+        Pipeline::new()
+            .dialect(cli.input_dialect()?)
+            .sheet(cli.sheet_options())
+            .progress(cli.progress_sink())
+            .from_file(path)
+    };
+    let old = load(old_path)?;
+    let new = load(new_path)?;
+    let keys: Vec<&str> = cli.keys.iter().map(String::as_str).collect();
+    // The diff file has its own columns next to the keys, which a key must not overwrite.
+    let columns = [
+        HEADER_DIFF_CHANGE,
+        HEADER_DIFF_COLUMN,
+        HEADER_DIFF_OLD,
+        HEADER_DIFF_NEW,
+    ];
+    if let Some(key) = keys.iter().find(|key| columns.contains(key)) {
+        return Err(format!(
+            "--keys column '{}' cannot be used, as the diff file has its own columns {}",
+            key,
+            columns.join(", ")
+        )
+        .into());
+    }
+    let report = old.diff(&new, keys.clone())?;
+
+    print_diff(&report, old.headers(), new.headers(), DIFF_PREVIEW_ROWS);
+    if cli.dry_run {
+        return Ok(());
+    }
+
+    // Added and removed rows leave column, old and new blank.
+    let headers = [HEADER_DIFF_CHANGE]
+        .into_iter()
+        .chain(keys)
+        .chain([HEADER_DIFF_COLUMN, HEADER_DIFF_OLD, HEADER_DIFF_NEW])
+        .collect();
+    Pipeline::from_records(diff_rows(&report))
+        .progress(cli.progress_sink())
+        .keep_incomplete(true)
+        .dialect(cli.output_dialect()?)
+        .format(cli.output_format())
+        .to_file(&cli.out_path, headers)?;
+    println!("Diff complete.");
+    Ok(())
+}
+
+/// Checks `rows` against a spec schema, if there is one. A missing required column stops
+/// the run, as no later step can make up for it.
+fn validate(
+    rows: Rows,
+    name: &str,
+    columns: &[ColumnSpec],
+    reports: &mut Vec<ValidationReport>,
+) -> Result<Rows, Box<dyn Error>> {
+    if columns.is_empty() {
+        return Ok(rows);
+    }
+    let (rows, report) = rows.validate(&schema(name, columns)?)?;
+    let missing = report.missing_columns();
+    if !missing.is_empty() {
+        println!("{}", report);
+        return Err(format!("{}: missing column(s) {}", name, missing.join(", ")).into());
+    }
+    reports.push(report);
+    Ok(rows)
+}
+
+fn apply_derives(
+    mut rows: Rows,
+    derives: &[(&str, &str)],
+    vars: &Vars,
+) -> Result<Rows, Box<dyn Error>> {
+    for (column, template) in derives {
+        rows = derive_template(rows, column, template, vars)?;
+    }
+    Ok(rows)
+}
+
+/// Streams the input files, or generates fake users into an in-memory pipeline.
+fn load(
+    cli: &Cli,
+    vars: &Vars,
+    input: &InputSpec,
+    policy: ErrorPolicy,
+) -> Result<Rows, Box<dyn Error>> {
+    if let Some(kind) = input.generate.as_deref() {
+        let count = match input.count.as_deref() {
+            Some(count) => vars.expand(count)?.trim().parse::<usize>()?,
+            None => cli.count,
+        };
+        let users = generate_users(kind, &cli.campus.to_string(), count)?;
+        let pipeline = Pipeline::new()
+            .progress(cli.progress_sink())
+            .parallel(cli.parallel)
+            .on_error(policy)
+            .with_rows(users)
+            .into_pipeline()?;
+        return Ok(Rows::Table(pipeline));
+    }
+
+    let paths = vars.expand_all(&input.paths)?;
+    if paths.is_empty() {
+        return Err("cli --in-path not defined".into());
+    }
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    let stream = RecordStream::from_files(
+        &paths,
+        &cli.input_dialect()?,
+        &cli.sheet_options(),
+        cli.source_column.as_deref(),
+    )?
+    .progress(cli.progress_sink())
+    .parallel(cli.parallel)
+    .on_error(policy);
+    Ok(Rows::Stream(stream))
+}
+
+fn generate_users(
+    kind: &str,
+    campus: &str,
+    count: usize,
+) -> Result<Vec<MoodleUser>, Box<dyn Error>> {
+    let username: fn(&str) -> String = match kind {
+        "students" => generate_username,
+        "faculty" => generate_faculty_username,
+        _ => {
+            return Err(format!(
+                "unknown generator '{}' (expected students or faculty)",
+                kind
+            )
+            .into());
+        }
+    };
+    Ok((0..count)
+        .map(|_| MoodleUser::fake(username(campus)))
+        .collect())
+}
+
+fn apply(
+    rows: Rows,
+    step: &Step,
+    cli: &Cli,
+    vars: &Vars,
+    kept: &mut Vec<String>,
+) -> Result<Rows, Box<dyn Error>> {
+    let rows = match step {
+        Step::Filter {
+            column,
+            equals,
+            not_equals,
+            one_of,
+            not_blank,
+        } => {
+            let column = column.clone();
+            let equals = equals.as_deref().map(|v| vars.expand(v)).transpose()?;
+            let not_equals = not_equals.as_deref().map(|v| vars.expand(v)).transpose()?;
+            let one_of = vars.expand_all(one_of)?;
+            let not_blank = *not_blank;
+            rows.filter(move |row| {
+                let value = row.get(&column).map(String::as_str).unwrap_or_default();
+                equals.as_deref().is_none_or(|equals| value == equals)
+                    && not_equals.as_deref() != Some(value)
+                    && (one_of.is_empty() || one_of.iter().any(|v| v == value))
+                    && (!not_blank || !value.trim().is_empty())
+            })
+        }
+        Step::Derive {
+            column,
+            value,
+            copy,
+            function,
+            template,
+            for_each: Some(list),
+        } => {
+            if copy.is_some() || function.is_some() || template.is_some() {
+                return Err(format!("derive {}: for_each only supports a value", column).into());
+            }
+            let value = value
+                .as_deref()
+                .ok_or_else(|| format!("derive {}: missing value", column))?;
+            let mut rows = rows;
+            for (idx, item) in vars.list(list)?.iter().enumerate() {
+                let header = column.replace("{n}", &(idx + 1).to_string());
+                let value = vars.expand_item(value, item)?;
+                rows = rows.derive(&header, move |_| value.clone());
+            }
+            rows
+        }
+        Step::Derive {
+            column,
+            value,
+            copy,
+            function,
+            template,
+            for_each: None,
+        } => match (value, copy, function, template) {
+            (Some(value), None, None, None) => {
+                let value = vars.expand(value)?;
+                rows.derive(column, move |_| value.clone())
+            }
+            (None, Some(source), None, None) => {
+                let source = source.clone();
+                rows.derive(column, move |row| {
+                    row.get(&source).cloned().unwrap_or_default()
+                })
+            }
+            (None, None, Some(function), None) => {
+                rows.try_derive(column, derive_function(function, cli)?)?
+            }
+            (None, None, None, Some(template)) => derive_template(rows, column, template, vars)?,
+            _ => {
+                return Err(format!(
+                    "derive {}: give exactly one of value, copy, function or template",
+                    column
+                )
+                .into());
+            }
+        },
+        Step::Select {
+            columns,
+            passthrough,
+            for_each,
+        } => {
+            let count = match for_each.as_deref() {
+                Some(name) => vars.list(name)?.len(),
+                None => 0,
+            };
+            let mut columns = expand_columns(&vars.expand_all(columns)?, count);
+            // Split, sample and --derive columns are kept on the rows so they can still be used;
+            // split and sample columns are not written unless selected. Missing ones are left
+            // out, so the split or sample step reports them.
+            kept.retain(|column| !columns.contains(column));
+            let derived: Vec<&str> = cli.derives()?.into_iter().map(|(name, _)| name).collect();
+            let extra = cli
+                .split_by
+                .iter()
+                .chain(&cli.sample_by)
+                .map(String::as_str)
+                .chain(derived.iter().copied());
+            for column in extra {
+                if rows.headers().iter().any(|h| h == column)
+                    && !columns.iter().any(|c| c == column)
+                {
+                    if !derived.contains(&column) {
+                        kept.push(column.to_string());
+                    }
+                    columns.push(column.to_string());
+                }
+            }
+            rows.select(columns.iter().map(String::as_str).collect(), *passthrough)
+        }
+        // Streaming resumes after steps that need every row, as row steps are cheaper there.
+        step => Rows::Stream(apply_in_memory(rows.into_pipeline()?, step, vars)?.into_stream()),
+    };
+    Ok(rows)
+}
+
+/// Steps that need every row at once.
+fn apply_in_memory(
+    pipeline: Pipeline,
+    step: &Step,
+    vars: &Vars,
+) -> Result<Pipeline, Box<dyn Error>> {
+    Ok(match step {
+        Step::Rename { columns } => pipeline.rename(
+            columns
+                .iter()
+                .map(|rename| (rename.from.as_str(), rename.to.as_str()))
+                .collect(),
+        )?,
+        Step::Reorder { columns } => {
+            pipeline.reorder(columns.iter().map(String::as_str).collect())?
+        }
+        Step::Cast { column, to } => pipeline.cast(column, to.parse::<CastType>()?)?,
+        Step::Sort { keys } => pipeline.sort_by(
+            keys.iter()
+                .map(|key| SortKey {
+                    column: key.column.clone(),
+                    descending: key.descending,
+                    natural: key.natural,
+                })
+                .collect(),
+        ),
+        Step::Dedupe { columns, strategy } => {
+            let strategy = match vars.expand(strategy)?.as_str() {
+                "keep-first" => DedupeStrategy::KeepFirst,
+                "keep-last" => DedupeStrategy::KeepLast,
+                "error" => DedupeStrategy::Error,
+                other => {
+                    return Err(format!(
+                        "unknown dedupe strategy '{}' (expected keep-first, keep-last or error)",
+                        other
+                    )
+                    .into());
+                }
+            };
+            let (pipeline, report) =
+                pipeline.dedupe_by(columns.iter().map(String::as_str).collect(), strategy)?;
+            if !report.is_empty() {
+                println!("{}", report);
+            }
+            pipeline
+        }
+        Step::Explode { column, delimiter } => pipeline.explode(column, delimiter),
+        Step::PivotLonger { stems } => {
+            pipeline.pivot_longer(stems.iter().map(String::as_str).collect())
+        }
+        Step::PivotWider { stems } => {
+            pipeline.pivot_wider(stems.iter().map(String::as_str).collect())
+        }
+        Step::Filter { .. } | Step::Derive { .. } | Step::Select { .. } => {
+            unreachable!("row steps are applied by apply")
+        }
+    })
+}
+
+/// Derives `column` from a [`Template`], after expanding `${name}` variables in it.
+/// Other single-valued variables can be used as `{name}`.
+fn derive_template(
+    rows: Rows,
+    column: &str,
+    template: &str,
+    vars: &Vars,
+) -> Result<Rows, Box<dyn Error>> {
+    let template = vars.bind(Template::parse(&vars.expand(template)?)?);
+    template
+        .check_columns(rows.headers())
+        .map_err(|err| format!("derive {}: {}", column, err))?;
+    Ok(rows.derive(column, move |row| template.render(row)))
+}
+
+/// Looks up a built-in derive function by name.
+fn derive_function(name: &str, cli: &Cli) -> Result<RowFn, Box<dyn Error>> {
+    let term = Term::from_cli(cli);
+    let function: RowFn = match name {
+        "course_shortname" => Box::new(move |row| {
+            Ok(term.shortname(field(row, HEADER_COURSE_CODE), field(row, HEADER_SEMESTER)))
+        }),
+        "category_path" => Box::new(move |row| {
+            term.category_path(field(row, HEADER_PROGRAM), field(row, HEADER_SEMESTER))
+        }),
+        "course_start_date" => Box::new(move |row| term.start_date(field(row, HEADER_SEMESTER))),
+        "course_end_date" => Box::new(move |row| term.end_date(field(row, HEADER_SEMESTER))),
+        _ => {
+            return Err(format!(
+                "unknown derive function '{}' (expected course_shortname, category_path, course_start_date or course_end_date)",
+                name
+            )
+            .into());
+        }
+    };
+    Ok(function)
+}
+
+/// Value of `header`, blank when the row has no such column; `--typed` runs catch that instead.
+fn field<'r>(row: &'r Record, header: &str) -> &'r str {
+    row.get(header).map(String::as_str).unwrap_or("")
+}
+
+/// Writes `headers` to `out_path`, or with `--split-by` one file per partition, using
+/// `out_path` as the path template.
+fn write_output(
+    pipeline: Pipeline,
+    cli: &Cli,
+    out_path: &str,
+    headers: Vec<&str>,
+) -> Result<(), Box<dyn Error>> {
+    if cli.split_by.is_empty() {
+        return pipeline.to_file(out_path, headers);
+    }
+    let columns = cli.split_by.iter().map(String::as_str).collect();
+    let paths = pipeline.split_by(columns, out_path, headers)?;
+    println!("Wrote {} files:", paths.len());
+    for path in paths {
+        println!("- {}", path);
+    }
+    Ok(())
+}
+
+/// Writes `size` random records of `pipeline`, or `size` per `--sample-by` group, to
+/// `--sample-path` for review. Incomplete records are kept, as they most need a look.
+fn write_sample(
+    pipeline: &Pipeline,
+    cli: &Cli,
+    size: usize,
+    headers: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    let path = cli
+        .sample_path
+        .as_deref()
+        .ok_or("cli --sample-path not defined")?;
+    let mut headers = headers.to_vec();
+    for column in &cli.sample_by {
+        if !pipeline.headers().contains(column) {
+            return Err(format!("--sample-by column '{}' is not in the output", column).into());
+        }
+        if !headers.contains(&column.as_str()) {
+            headers.push(column);
+        }
+    }
+
+    let columns = cli.sample_by.iter().map(String::as_str).collect();
+    let sample = Pipeline::from_records(pipeline.records().to_vec())
+        .progress(cli.progress_sink())
+        .select(headers.clone())
+        .sample_by(columns, size, cli.seed)?;
+    println!(
+        "Sampled {} of {} records for review to {}",
+        sample.len(),
+        pipeline.len(),
+        path
+    );
+    sample
+        .keep_incomplete(true)
+        .dialect(cli.output_dialect()?)
+        .to_file(path, headers)
+}
+
+/// Dumps the step lineage to `--lineage-path`, if given.
+fn write_lineage(cli: &Cli, lineage: &Lineage) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = cli.lineage_path.as_deref() {
+        lineage.write_json(path)?;
+        println!("Wrote lineage of {} steps to {}", lineage.len(), path);
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use crate::Pipeline;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index;
+use std::collections::HashMap;
+use std::error::Error;
+
+impl Pipeline {
+    /// Keeps `size` rows picked at random, in their original order, or every row if there
+    /// are fewer. The same `seed` picks the same rows from the same input.
+    pub fn sample(self, size: usize, seed: Option<u64>) -> Result<Self, Box<dyn Error>> {
+        self.sample_by(Vec::new(), size, seed)
+    }
+
+    /// Like [`Pipeline::sample`], but keeps up to `size` rows for each distinct value of
+    /// `columns`, such as a few courses from every program and year. Fails if one of
+    /// `columns` is missing.
+    pub fn sample_by(
+        mut self,
+        columns: Vec<&str>,
+        size: usize,
+        seed: Option<u64>,
+    ) -> Result<Self, Box<dyn Error>> {
+        if let Some(column) = columns
+            .iter()
+            .find(|column| !self.headers.iter().any(|h| h == *column))
+        {
+            return Err(format!("sample_by: the rows have no column '{}'", column).into());
+        }
+        let step = if columns.is_empty() {
+            format!("sample {}", size)
+        } else {
+            format!("sample_by {} ({} each)", columns.join(", "), size)
+        };
+        let step = self.begin_step(step);
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let pb = self
+            .progress
+            .start("Sampling records...", Some(self.records.len() as u64));
+        let mut strata: Vec<Vec<usize>> = Vec::new();
+        let mut stratum_of: HashMap<Vec<&str>, usize> = HashMap::new();
+        for (idx, record) in self.records.iter().enumerate() {
+            let key: Vec<&str> = columns
+                .iter()
+                .map(|column| record.get(column).map(String::as_str).unwrap_or(""))
+                .collect();
+            let stratum = *stratum_of.entry(key).or_insert_with(|| {
+                strata.push(Vec::new());
+                strata.len() - 1
+            });
+            strata[stratum].push(idx);
+            pb.inc(1);
+        }
+
+        let mut keep = vec![false; self.records.len()];
+        for rows in &strata {
+            for picked in index::sample(&mut rng, rows.len(), size.min(rows.len())) {
+                keep[rows[picked]] = true;
+            }
+        }
+        let total = self.records.len();
+        let mut keep = keep.into_iter();
+        self.records.retain(|_| keep.next().unwrap_or(false));
+
+        pb.finish(&format!(
+            "Sampled {} of {} records",
+            self.records.len(),
+            total
+        ));
+        self.end_step(step);
+        Ok(self)
+    }
+}
@@ -0,0 +1,294 @@
+use crate::errors::location;
+use crate::{ErrorPolicy, Pipeline, Record, RowError};
+use regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Row problems listed in full by a [`ValidationReport`]; the rest are only counted.
+const SHOWN_PROBLEMS: usize = 20;
+
+/// What one column must look like. Blank values only fail [`ColumnRule::not_blank`].
+#[derive(Clone, Debug)]
+pub struct ColumnRule {
+    pub column: String,
+    /// The column must exist; rules on a missing optional column are skipped.
+    pub required: bool,
+    pub not_blank: bool,
+    /// Values the column may hold; empty allows any value.
+    pub allowed: Vec<String>,
+    /// Pattern the whole value must match, compiled and as written for messages; set with
+    /// [`ColumnRule::pattern`] so the two always agree.
+    pattern: Option<(Regex, String)>,
+    /// No two rows may share a value.
+    pub unique: bool,
+}
+
+impl ColumnRule {
+    pub fn required(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            required: true,
+            not_blank: false,
+            allowed: Vec::new(),
+            pattern: None,
+            unique: false,
+        }
+    }
+
+    pub fn optional(column: &str) -> Self {
+        Self {
+            required: false,
+            ..Self::required(column)
+        }
+    }
+
+    pub fn not_blank(mut self) -> Self {
+        self.not_blank = true;
+        self
+    }
+
+    pub fn allowed(mut self, values: &[&str]) -> Self {
+        self.allowed = values.iter().map(|value| value.to_string()).collect();
+        self
+    }
+
+    pub fn pattern(mut self, pattern: &str) -> Result<Self, Box<dyn Error>> {
+        let anchored = format!("^(?:{})$", pattern);
+        let regex = Regex::new(&anchored)
+            .map_err(|err| format!("invalid pattern for '{}': {}", self.column, err))?;
+        self.pattern = Some((regex, pattern.to_string()));
+        Ok(self)
+    }
+
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Why `value` breaks this rule, ignoring uniqueness.
+    fn problem(&self, value: &str) -> Option<String> {
+        if value.trim().is_empty() {
+            return self.not_blank.then(|| "is blank".to_string());
+        }
+        if !self.allowed.is_empty() && !self.allowed.iter().any(|allowed| allowed == value) {
+            return Some(format!(
+                "'{}' is not one of {}",
+                value,
+                self.allowed.join(", ")
+            ));
+        }
+        if let Some((regex, pattern)) = &self.pattern
+            && !regex.is_match(value)
+        {
+            return Some(format!("'{}' does not match {}", value, pattern));
+        }
+        None
+    }
+}
+
+/// Columns a file is expected to have, and what their values may be.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    pub name: String,
+    pub columns: Vec<ColumnRule>,
+}
+
+impl Schema {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn column(mut self, rule: ColumnRule) -> Self {
+        self.columns.push(rule);
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Findings {
+    missing: Vec<String>,
+    problems: Vec<RowError>,
+}
+
+/// Missing columns and row problems found against a [`Schema`].
+///
+/// Like [`crate::ErrorLog`], clones share the same findings, so the handle returned by
+/// `RecordStream::validate` fills in as the stream is consumed.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    schema: String,
+    findings: Arc<Mutex<Findings>>,
+}
+
+impl ValidationReport {
+    fn new(schema: &str) -> Self {
+        Self {
+            schema: schema.to_string(),
+            findings: Arc::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let findings = self.findings.lock().unwrap();
+        findings.missing.is_empty() && findings.problems.is_empty()
+    }
+
+    /// Required columns the input did not have.
+    pub fn missing_columns(&self) -> Vec<String> {
+        self.findings.lock().unwrap().missing.clone()
+    }
+
+    pub fn problems(&self) -> Vec<RowError> {
+        self.findings.lock().unwrap().problems.clone()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let findings = self.findings.lock().unwrap();
+        write!(
+            f,
+            "Schema '{}': {} missing column(s), {} row problem(s)",
+            self.schema,
+            findings.missing.len(),
+            findings.problems.len()
+        )?;
+        for column in &findings.missing {
+            write!(f, "\n- missing column '{}'", column)?;
+        }
+
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for problem in &findings.problems {
+            match counts
+                .iter_mut()
+                .find(|(column, _)| *column == problem.column)
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((&problem.column, 1)),
+            }
+        }
+        for (column, count) in counts {
+            write!(f, "\n- {}: {} row(s)", column, count)?;
+        }
+        for problem in findings.problems.iter().take(SHOWN_PROBLEMS) {
+            write!(f, "\n  {}", problem)?;
+        }
+        if findings.problems.len() > SHOWN_PROBLEMS {
+            write!(
+                f,
+                "\n  ... and {} more",
+                findings.problems.len() - SHOWN_PROBLEMS
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationReport {}
+
+/// Checks rows one at a time against a [`Schema`], remembering values of unique columns.
+pub(crate) struct Validator {
+    /// Rules whose column is present.
+    rules: Vec<ColumnRule>,
+    /// Per rule, the location of the first row holding each value of a unique column.
+    seen: Vec<HashMap<String, String>>,
+    report: ValidationReport,
+}
+
+impl Validator {
+    /// Starts validating rows with `headers`, recording missing required columns at once.
+    pub(crate) fn new(schema: &Schema, headers: &[String]) -> Self {
+        let report = ValidationReport::new(&schema.name);
+        let mut rules = Vec::new();
+        for rule in &schema.columns {
+            if headers.contains(&rule.column) {
+                rules.push(rule.clone());
+            } else if rule.required {
+                report
+                    .findings
+                    .lock()
+                    .unwrap()
+                    .missing
+                    .push(rule.column.clone());
+            }
+        }
+        Self {
+            seen: vec![HashMap::new(); rules.len()],
+            rules,
+            report,
+        }
+    }
+
+    pub(crate) fn report(&self) -> ValidationReport {
+        self.report.clone()
+    }
+
+    /// Records every problem of row `idx` and returns the first one.
+    pub(crate) fn check(&mut self, idx: usize, record: &Record) -> Option<RowError> {
+        let mut first = None;
+        for (rule, seen) in self.rules.iter().zip(&mut self.seen) {
+            let value = record.get(&rule.column).map(String::as_str).unwrap_or("");
+            let mut problem = rule.problem(value);
+            if problem.is_none() && rule.unique && !value.trim().is_empty() {
+                match seen.get(value) {
+                    Some(first_seen) => {
+                        problem = Some(format!("'{}' repeats {}", value, first_seen));
+                    }
+                    None => {
+                        seen.insert(value.to_string(), location(record, idx));
+                    }
+                }
+            }
+
+            if let Some(message) = problem {
+                let error = RowError::new(record, idx, &rule.column, message);
+                self.report
+                    .findings
+                    .lock()
+                    .unwrap()
+                    .problems
+                    .push(error.clone());
+                first.get_or_insert(error);
+            }
+        }
+        first
+    }
+}
+
+impl Pipeline {
+    /// Checks the columns and every row against `schema`.
+    ///
+    /// Under [`ErrorPolicy::Abort`] a missing required column or the first row problem
+    /// fails the step; otherwise everything found is returned in the report.
+    pub fn validate(&self, schema: &Schema) -> Result<ValidationReport, Box<dyn Error>> {
+        let step = self.begin_step(format!("validate {}", schema.name));
+        let mut validator = Validator::new(schema, &self.headers);
+        let report = validator.report();
+        if self.policy == ErrorPolicy::Abort && !report.missing_columns().is_empty() {
+            return Err(report.into());
+        }
+
+        let pb = self.progress.start(
+            &format!("Validating against {}...", schema.name),
+            Some(self.records.len() as u64),
+        );
+        for (idx, record) in self.records.iter().enumerate() {
+            if let Some(error) = validator.check(idx, record)
+                && self.policy == ErrorPolicy::Abort
+            {
+                pb.abandon(&format!("Failed validating against {}", schema.name));
+                return Err(error.into());
+            }
+            pb.inc(1);
+        }
+
+        pb.finish(&format!("Validated against {}", schema.name));
+        self.end_step(step);
+        Ok(report)
+    }
+}
@@ -0,0 +1,115 @@
+use crate::Pipeline;
+use std::cmp::Ordering;
+
+/// One column of a [`Pipeline::sort_by`] ordering.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+    /// Compare runs of digits by numeric value, so `CS 2` sorts before `CS 10`.
+    pub natural: bool,
+}
+
+impl SortKey {
+    pub fn asc(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            descending: false,
+            natural: false,
+        }
+    }
+
+    pub fn desc(column: &str) -> Self {
+        Self {
+            descending: true,
+            ..Self::asc(column)
+        }
+    }
+
+    pub fn natural(mut self) -> Self {
+        self.natural = true;
+        self
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        let ordering = if self.natural {
+            natural_cmp(a, b)
+        } else {
+            a.cmp(b)
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Compares strings chunk by chunk, treating runs of ASCII digits as numbers.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_digits = String::new();
+                while let Some(c) = a.next_if(char::is_ascii_digit) {
+                    x_digits.push(c);
+                }
+                let mut y_digits = String::new();
+                while let Some(c) = b.next_if(char::is_ascii_digit) {
+                    y_digits.push(c);
+                }
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+impl Pipeline {
+    /// Stable sort on several columns; rows equal on every key keep their current order.
+    pub fn sort_by(mut self, keys: Vec<SortKey>) -> Self {
+        let columns: Vec<&str> = keys.iter().map(|key| key.column.as_str()).collect();
+        let step = self.begin_step(format!("sort_by {}", columns.join(", ")));
+        let pb = self.progress.start(
+            &format!("Sorting by {}...", columns.join(", ")),
+            Some(self.records.len() as u64),
+        );
+
+        self.records.sort_by(|a, b| {
+            keys.iter()
+                .map(|key| {
+                    let x = a.get(&key.column).map(String::as_str).unwrap_or("");
+                    let y = b.get(&key.column).map(String::as_str).unwrap_or("");
+                    key.compare(x, y)
+                })
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        pb.inc(self.records.len() as u64);
+        pb.finish(&format!("Sorted {} records", self.records.len()));
+        self.end_step(step);
+        self
+    }
+}
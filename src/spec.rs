@@ -0,0 +1,370 @@
+use crate::cli::Cli;
+use clap::ValueEnum;
+use moodle_course_builder::{ColumnRule, Schema, Template};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Specs shipped with the tool, one per `--mode`, runnable by name with `--spec`.
+pub const BUILTIN_SPECS: [(&str, &str); 7] = [
+    ("course", include_str!("../specs/course.toml")),
+    ("user-seed", include_str!("../specs/user-seed.toml")),
+    ("user-enrol", include_str!("../specs/user-enrol.toml")),
+    ("faculty-seed", include_str!("../specs/faculty-seed.toml")),
+    ("faculty-enrol", include_str!("../specs/faculty-enrol.toml")),
+    ("enrol-longer", include_str!("../specs/enrol-longer.toml")),
+    ("enrol-wider", include_str!("../specs/enrol-wider.toml")),
+];
+
+/// A pipeline described in a TOML or YAML file: where rows come from, the steps applied
+/// to them in order, and where they are written.
+///
+/// Strings may refer to command line values as `${name}`; see [`Vars`].
+#[derive(Debug, Deserialize)]
+pub struct Spec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub input: InputSpec,
+    #[serde(default)]
+    pub steps: Vec<StepSpec>,
+    #[serde(default)]
+    pub output: OutputSpec,
+}
+
+/// Input files, or a generator of fake users when `generate` is set.
+#[derive(Debug, Default, Deserialize)]
+pub struct InputSpec {
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// `students` or `faculty`.
+    pub generate: Option<String>,
+    /// Number of generated users.
+    pub count: Option<String>,
+    /// Rules the input is checked against before any step runs.
+    #[serde(default)]
+    pub schema: Vec<ColumnSpec>,
+}
+
+/// A [`ColumnRule`] as written in a spec: required unless `optional`.
+#[derive(Debug, Deserialize)]
+pub struct ColumnSpec {
+    pub column: String,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub not_blank: bool,
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+/// Builds the [`Schema`] named `name` from spec column rules.
+pub fn schema(name: &str, columns: &[ColumnSpec]) -> Result<Schema, Box<dyn Error>> {
+    let mut schema = Schema::new(name);
+    for spec in columns {
+        let mut rule = if spec.optional {
+            ColumnRule::optional(&spec.column)
+        } else {
+            ColumnRule::required(&spec.column)
+        };
+        rule.not_blank = spec.not_blank;
+        rule.unique = spec.unique;
+        rule.allowed = spec.allowed.clone();
+        if let Some(pattern) = &spec.pattern {
+            rule = rule.pattern(pattern)?;
+        }
+        schema = schema.column(rule);
+    }
+    Ok(schema)
+}
+
+/// A step, skipped unless the `when` variable is set and unless the `unless` one is not.
+#[derive(Debug, Deserialize)]
+pub struct StepSpec {
+    pub when: Option<String>,
+    pub unless: Option<String>,
+    #[serde(flatten)]
+    pub step: Step,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    /// Keeps rows where `column` matches every condition given.
+    Filter {
+        column: String,
+        equals: Option<String>,
+        not_equals: Option<String>,
+        #[serde(default)]
+        one_of: Vec<String>,
+        #[serde(default)]
+        not_blank: bool,
+    },
+    /// Sets `column` to a fixed `value`, a `copy` of another column, the result of a
+    /// built-in `function`, or a `template` such as `{Course Code|nospace|upper}`. With
+    /// `for_each`, repeats once per item of that list variable, replacing `{n}` in the
+    /// column with the 1-based index and `${item}` with the item.
+    Derive {
+        column: String,
+        value: Option<String>,
+        copy: Option<String>,
+        function: Option<String>,
+        template: Option<String>,
+        for_each: Option<String>,
+    },
+    Rename {
+        columns: Vec<RenameSpec>,
+    },
+    Reorder {
+        columns: Vec<String>,
+    },
+    Cast {
+        column: String,
+        to: String,
+    },
+    Sort {
+        keys: Vec<SortSpec>,
+    },
+    /// Drops rows with repeated `columns`; `strategy` is `keep-first`, `keep-last` or `error`.
+    Dedupe {
+        columns: Vec<String>,
+        strategy: String,
+    },
+    /// Keeps `columns` in order, plus every other column after them with `passthrough`.
+    /// `{n}` columns are expanded over the `for_each` list variable.
+    Select {
+        columns: Vec<String>,
+        #[serde(default)]
+        passthrough: bool,
+        for_each: Option<String>,
+    },
+    Explode {
+        column: String,
+        delimiter: String,
+    },
+    PivotLonger {
+        stems: Vec<String>,
+    },
+    PivotWider {
+        stems: Vec<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameSpec {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SortSpec {
+    pub column: String,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub natural: bool,
+}
+
+/// Where and what to write. Without `headers` every selected column is written.
+#[derive(Debug, Deserialize)]
+pub struct OutputSpec {
+    #[serde(default = "default_output_path")]
+    pub path: String,
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// List variable `{n}` headers are expanded over.
+    pub for_each: Option<String>,
+    /// Summarize blank output values in dry runs.
+    #[serde(default)]
+    pub report_missing: bool,
+    /// Rules the output is checked against before it is written.
+    #[serde(default)]
+    pub schema: Vec<ColumnSpec>,
+}
+
+impl Default for OutputSpec {
+    fn default() -> Self {
+        Self {
+            path: default_output_path(),
+            headers: Vec::new(),
+            for_each: None,
+            report_missing: false,
+            schema: Vec::new(),
+        }
+    }
+}
+
+fn default_output_path() -> String {
+    "${out_path}".to_string()
+}
+
+impl Spec {
+    /// Reads a spec file, or a built-in spec when `name` is one of [`BUILTIN_SPECS`] and
+    /// no such file exists.
+    pub fn load(name: &str) -> Result<Self, Box<dyn Error>> {
+        if !Path::new(name).exists()
+            && let Some(spec) = Self::builtin(name)
+        {
+            return spec;
+        }
+        let text = std::fs::read_to_string(name)
+            .map_err(|err| format!("cannot read spec '{}': {}", name, err))?;
+        Self::parse(&text, is_yaml(name)).map_err(|err| format!("{}: {}", name, err).into())
+    }
+
+    pub fn builtin(name: &str) -> Option<Result<Self, Box<dyn Error>>> {
+        BUILTIN_SPECS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, text)| Self::parse(text, false))
+    }
+
+    fn parse(text: &str, yaml: bool) -> Result<Self, Box<dyn Error>> {
+        if yaml {
+            Ok(serde_yaml::from_str(text)?)
+        } else {
+            Ok(toml::from_str(text)?)
+        }
+    }
+}
+
+fn is_yaml(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+}
+
+/// Values a spec can refer to as `${name}`: the command line options plus `--set` pairs.
+/// List options such as `in_path` and `courses` hold several values.
+pub struct Vars {
+    values: HashMap<String, Vec<String>>,
+}
+
+impl Vars {
+    pub fn from_cli(cli: &Cli) -> Result<Self, Box<dyn Error>> {
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        values.insert("in_path".to_string(), cli.in_path.clone());
+        values.insert("out_path".to_string(), vec![cli.out_path.clone()]);
+        values.insert("campus".to_string(), vec![cli.campus.to_string()]);
+        values.insert("start_date".to_string(), vec![cli.start_date.clone()]);
+        values.insert("end_date".to_string(), vec![cli.end_date.clone()]);
+        values.insert("count".to_string(), vec![cli.count.to_string()]);
+        values.insert("courses".to_string(), cli.courses.clone());
+        if let Some(dept) = &cli.dept {
+            values.insert("dept".to_string(), vec![dept.clone()]);
+        }
+        if let Some(mode) = cli.dedupe.and_then(|mode| mode.to_possible_value()) {
+            values.insert("dedupe".to_string(), vec![mode.get_name().to_string()]);
+        }
+        for (name, set) in [("sort", cli.sort), ("passthrough", cli.passthrough)] {
+            if set {
+                values.insert(name.to_string(), vec!["true".to_string()]);
+            }
+        }
+
+        for pair in &cli.set {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("--set expects NAME=VALUE, got '{}'", pair))?;
+            values.insert(name.trim().to_string(), vec![value.to_string()]);
+        }
+        Ok(Self { values })
+    }
+
+    /// Whether `name` has a value other than blank or `false`.
+    pub fn is_set(&self, name: &str) -> bool {
+        self.values
+            .get(name)
+            .is_some_and(|values| values.iter().any(|v| !v.is_empty() && v != "false"))
+    }
+
+    pub fn list(&self, name: &str) -> Result<&[String], Box<dyn Error>> {
+        self.values
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("unknown spec variable '{}'", name).into())
+    }
+
+    /// Replaces every `${name}` in `text`; list variables must hold exactly one value.
+    pub fn expand(&self, text: &str) -> Result<String, Box<dyn Error>> {
+        let mut expanded = String::new();
+        let mut rest = text;
+        while let Some(open) = rest.find("${") {
+            let close = rest[open..]
+                .find('}')
+                .map(|close| open + close)
+                .ok_or_else(|| format!("unclosed '${{' in '{}'", text))?;
+            expanded.push_str(&rest[..open]);
+
+            let name = &rest[open + 2..close];
+            match self.list(name)? {
+                [value] => expanded.push_str(value),
+                [] => return Err(format!("spec variable '{}' is not set", name).into()),
+                _ => return Err(format!("spec variable '{}' holds several values", name).into()),
+            }
+            rest = &rest[close + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Expands a list of strings; an entry that is exactly `${name}` takes every value of
+    /// a list variable.
+    pub fn expand_all(&self, texts: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut expanded = Vec::with_capacity(texts.len());
+        for text in texts {
+            match text.strip_prefix("${").and_then(|t| t.strip_suffix('}')) {
+                Some(name) if !name.contains('}') => {
+                    expanded.extend(self.list(name)?.iter().cloned())
+                }
+                _ => expanded.push(self.expand(text)?),
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Binds every single-valued variable, so templates can use `{campus}`.
+    pub fn bind(&self, mut template: Template) -> Template {
+        for (name, values) in &self.values {
+            if let [value] = values.as_slice() {
+                template = template.bind(name, value);
+            }
+        }
+        template
+    }
+
+    /// Like [`Vars::expand`] with `${item}` bound to `item`.
+    pub fn expand_item(&self, text: &str, item: &str) -> Result<String, Box<dyn Error>> {
+        self.expand(&text.replace("${item}", item))
+    }
+}
+
+/// Repeats each run of consecutive `{n}` columns `count` times, numbering from 1, so
+/// `[username, course{n}, role{n}]` becomes `[username, course1, role1, course2, role2]`.
+pub fn expand_columns(columns: &[String], count: usize) -> Vec<String> {
+    let mut expanded = Vec::new();
+    let mut idx = 0;
+    while idx < columns.len() {
+        if !columns[idx].contains("{n}") {
+            expanded.push(columns[idx].clone());
+            idx += 1;
+            continue;
+        }
+        let end = columns[idx..]
+            .iter()
+            .position(|column| !column.contains("{n}"))
+            .map_or(columns.len(), |len| idx + len);
+        for n in 1..=count {
+            for column in &columns[idx..end] {
+                expanded.push(column.replace("{n}", &n.to_string()));
+            }
+        }
+        idx = end;
+    }
+    expanded
+}
@@ -0,0 +1,133 @@
+use crate::writer::{OutputWriter, output_row};
+use crate::{Pipeline, Template, WriteOptions};
+use indexmap::IndexMap;
+use std::error::Error;
+
+/// Makes a column value safe to use as one path segment; blank values become `blank`.
+fn path_segment(value: String) -> String {
+    let segment: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match segment.as_str() {
+        "" | "." | ".." => "blank".to_string(),
+        _ => segment,
+    }
+}
+
+impl Pipeline {
+    /// Writes one file per distinct combination of `columns`, at `path_template` rendered
+    /// as a [`Template`] for the rows of each file, e.g.
+    /// `parsed_courses/ucmn/ccs/{Program|lower}/{Program|lower}_courses.csv`. Columns the
+    /// template uses split the rows too, so every row lands in the file its values name.
+    ///
+    /// A rejects path set with [`Pipeline::rejects_to`] is expanded the same way. Returns the
+    /// written paths in order of first appearance.
+    pub fn split_by(
+        self,
+        columns: Vec<&str>,
+        path_template: &str,
+        headers: Vec<&str>,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let template = Template::parse(path_template)?;
+        let rejects_template = match &self.write_options.rejects_path {
+            Some(path) => Some(Template::parse(path)?),
+            None => None,
+        };
+        for &column in &columns {
+            if !self.headers.iter().any(|header| header == column) {
+                return Err(format!("cannot split on unknown column '{}'", column).into());
+            }
+        }
+        let mut split_columns = columns.clone();
+        for template in std::iter::once(&template).chain(&rejects_template) {
+            template.check_columns(&self.headers)?;
+            for column in template.columns() {
+                if !split_columns.contains(&column) {
+                    split_columns.push(column);
+                }
+            }
+        }
+
+        let step = self.begin_step(format!("split_by {}", columns.join(", ")));
+        let mut partitions: IndexMap<Vec<&str>, Vec<usize>> = IndexMap::new();
+        for (idx, record) in self.records.iter().enumerate() {
+            let key = split_columns
+                .iter()
+                .map(|&column| record.get(column).map(String::as_str).unwrap_or_default())
+                .collect();
+            partitions.entry(key).or_default().push(idx);
+        }
+
+        // Paths come from the first row of each partition; two partitions must not share one.
+        let mut targets: Vec<(String, WriteOptions, &[usize])> = Vec::new();
+        for (key, indices) in &partitions {
+            let first = &self.records[indices[0]];
+            let path = template.render_escaped(first, &path_segment);
+            let mut options = self.write_options.clone();
+            options.rejects_path = rejects_template
+                .as_ref()
+                .map(|t| t.render_escaped(first, &path_segment));
+
+            for (other, other_options, _) in &targets {
+                if *other == path
+                    || (options.rejects_path.is_some()
+                        && options.rejects_path == other_options.rejects_path)
+                {
+                    return Err(format!(
+                        "partition [{}] writes to the same file as another partition; add its columns to the path template",
+                        key.join(", ")
+                    )
+                    .into());
+                }
+            }
+            targets.push((path, options, indices));
+        }
+
+        let pb = self.progress.start(
+            &format!("Splitting by {}...", columns.join(", ")),
+            Some(self.records.len() as u64),
+        );
+        let mut written = 0;
+        let mut paths = Vec::with_capacity(targets.len());
+        for (path, options, indices) in targets {
+            let mut writer = OutputWriter::create(
+                &path,
+                &headers,
+                &self.passthrough,
+                &options,
+                &self.dialect,
+                &self.errors,
+            )?;
+            for &idx in indices {
+                if let Err(err) = writer.write(&self.records[idx], idx) {
+                    pb.abandon(&format!("Failed writing to {}", path));
+                    return Err(err);
+                }
+                pb.inc(1);
+            }
+            written += writer.written;
+            writer.finish()?;
+            paths.push(path);
+        }
+
+        pb.finish(&format!(
+            "Split {} records into {} files",
+            self.records.len(),
+            paths.len()
+        ));
+        step.finish(
+            &self.lineage,
+            written,
+            &output_row(&headers, &self.passthrough),
+        );
+        Ok(paths)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{Position, Record};
+use calamine::{Data, Reader, open_workbook_auto};
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+const SPREADSHEET_EXTENSIONS: [&str; 4] = ["xlsx", "xlsm", "xls", "ods"];
+
+/// Which part of a workbook is read as a table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SheetOptions {
+    /// Sheet to read; the first sheet when `None`.
+    pub sheet: Option<String>,
+    /// 1-based row holding the headers; the first row with a value in it when `None`.
+    /// Rows above it are ignored.
+    pub header_row: Option<u32>,
+}
+
+/// Whether `path` has a workbook extension that [`read_sheet`] understands.
+pub fn is_spreadsheet(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SPREADSHEET_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Renders a cell the way it would appear in a CSV export of the sheet.
+fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::Empty | Data::Error(_) => String::new(),
+        Data::String(value) | Data::DateTimeIso(value) | Data::DurationIso(value) => value.clone(),
+        Data::Int(value) => value.to_string(),
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+            (*value as i64).to_string()
+        }
+        Data::Float(value) => value.to_string(),
+        Data::Bool(value) => value.to_string(),
+        Data::DateTime(value) => match value.as_datetime() {
+            Some(datetime) if datetime.time() == chrono::NaiveTime::MIN => {
+                datetime.format("%Y-%m-%d").to_string()
+            }
+            Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => value.as_f64().to_string(),
+        },
+    }
+}
+
+/// Reads one sheet of an `.xlsx`/`.xls`/`.ods` workbook into headers and records.
+///
+/// Record positions point at `path[sheet]` and the 1-based sheet row. Blank header cells
+/// are named `column_N` after their 1-based column; repeated header names are an error.
+pub fn read_sheet(
+    path: &str,
+    options: &SheetOptions,
+) -> Result<(Vec<String>, Vec<Record>), Box<dyn Error>> {
+    let mut workbook = open_workbook_auto(path)?;
+    let sheet = match &options.sheet {
+        Some(sheet) => sheet.clone(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| format!("{} has no sheets", path))?,
+    };
+    let range = workbook.worksheet_range(&sheet)?;
+    let source: Arc<str> = Arc::from(format!("{}[{}]", path, sheet));
+
+    let (first_row, first_column) = range
+        .start()
+        .map(|(row, column)| (row + 1, column + 1))
+        .unwrap_or((1, 1));
+    let header_row = options.header_row.map_or(first_row, |row| row.max(1));
+    let mut rows = range
+        .rows()
+        .enumerate()
+        .map(|(idx, cells)| (first_row + idx as u32, cells))
+        .skip_while(|(row, _)| *row < header_row);
+
+    let mut headers: Vec<String> = match rows.next() {
+        Some((row, cells)) if row == header_row => cells.iter().map(cell_to_string).collect(),
+        _ => Vec::new(),
+    };
+    if headers.iter().all(|header| header.trim().is_empty()) {
+        return Err(format!("{}: header row {} is empty", source, header_row).into());
+    }
+    for (idx, header) in headers.iter_mut().enumerate() {
+        if header.trim().is_empty() {
+            *header = format!("column_{}", first_column + idx as u32);
+        }
+    }
+    for (idx, header) in headers.iter().enumerate() {
+        if headers[..idx].contains(header) {
+            return Err(format!(
+                "{}: header row {} has more than one column '{}'",
+                source, header_row, header
+            )
+            .into());
+        }
+    }
+
+    let mut records = Vec::new();
+    for (row, cells) in rows {
+        if cells.iter().all(|cell| matches!(cell, Data::Empty)) {
+            continue;
+        }
+        let mut record: Record = headers
+            .iter()
+            .cloned()
+            .zip(cells.iter().map(cell_to_string))
+            .collect();
+        record.set_position(Some(Position {
+            path: Arc::clone(&source),
+            line: u64::from(row),
+        }));
+        records.push(record);
+    }
+
+    Ok((headers, records))
+}
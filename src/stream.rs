@@ -0,0 +1,515 @@
+use crate::format::{ndjson_rows, read_json};
+use crate::input::check_headers;
+use crate::lineage::{column_changes, millis};
+use crate::parallel::{CHUNK_SIZE, Chunks};
+use crate::schema::{Schema, ValidationReport, Validator};
+use crate::spreadsheet::read_sheet;
+use crate::writer::{OutputWriter, output_row};
+use crate::{
+    BarProgress, Dialect, ErrorLog, ErrorPolicy, FileFormat, Lineage, Pipeline, ProgressSink,
+    Record, RowError, SheetOptions, StepRecord, WriteOptions, expand_paths, finish_message,
+    headers_of, is_spreadsheet, passthrough_of, record_from_row,
+};
+use rayon::prelude::*;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+
+type RowResult = Result<Record, Box<dyn Error>>;
+
+/// Iterator-backed counterpart of [`Pipeline`].
+///
+/// Every step wraps the previous iterator, so a `from_file` → `filter` → `derive` → `select`
+/// → `to_file` chain holds a single row in memory at a time. Use [`RecordStream::collect`]
+/// to fall back to a [`Pipeline`] for steps that need the whole data set.
+pub struct RecordStream<'a> {
+    rows: Box<dyn Iterator<Item = RowResult> + 'a>,
+    headers: Vec<String>,
+    passthrough: Vec<String>,
+    policy: ErrorPolicy,
+    errors: ErrorLog,
+    write_options: WriteOptions,
+    dialect: Dialect,
+    progress: Arc<dyn ProgressSink>,
+    parallel: bool,
+    lineage: Lineage,
+}
+
+impl<'a> RecordStream<'a> {
+    pub fn from_records(records: Vec<Record>) -> Self {
+        let headers = headers_of(&records);
+        Self::from_parts(records, headers, Vec::new())
+    }
+
+    pub(crate) fn from_parts(
+        records: Vec<Record>,
+        headers: Vec<String>,
+        passthrough: Vec<String>,
+    ) -> Self {
+        Self {
+            rows: Box::new(records.into_iter().map(Ok)),
+            headers,
+            passthrough,
+            policy: ErrorPolicy::default(),
+            errors: ErrorLog::default(),
+            write_options: WriteOptions::default(),
+            dialect: Dialect::default(),
+            progress: Arc::new(BarProgress),
+            parallel: false,
+            lineage: Lineage::default(),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_file_with(path, &Dialect::default())
+    }
+
+    /// Streams the rows of a workbook sheet. The sheet is loaded whole, since workbooks
+    /// cannot be read row by row.
+    pub fn from_spreadsheet(path: &str, sheet: &SheetOptions) -> Result<Self, Box<dyn Error>> {
+        let (headers, records) = read_sheet(path, sheet)?;
+        Ok(Self::from_parts(records, headers, Vec::new()).source(format!("from_file {}", path)))
+    }
+
+    /// Streams `path` read with `dialect`, which is also kept for `to_file`.
+    ///
+    /// `.json` files are loaded whole and `.ndjson` files read line by line, taking their
+    /// headers from the first object; both ignore the dialect.
+    pub fn from_file_with(path: &str, dialect: &Dialect) -> Result<Self, Box<dyn Error>> {
+        let stream = Self::open(path, dialect, &SheetOptions::default())?;
+        Ok(stream.source(format!("from_file {}", path)))
+    }
+
+    /// Streams several files or glob patterns one after another; see
+    /// [`Pipeline::from_files`]. Every file is opened and its headers checked before the
+    /// first row is read. With `source_column`, each row gets its file path in that column.
+    pub fn from_files(
+        patterns: &[&str],
+        dialect: &Dialect,
+        sheet: &SheetOptions,
+        source_column: Option<&str>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let paths = expand_paths(patterns)?;
+        let mut first: Option<(String, Vec<String>)> = None;
+        let mut rows: Box<dyn Iterator<Item = RowResult> + 'a> = Box::new(std::iter::empty());
+
+        for path in &paths {
+            let stream = Self::open(path, dialect, sheet)?;
+            match &first {
+                Some((first, expected)) => check_headers(first, expected, path, &stream.headers)?,
+                None => first = Some((path.clone(), stream.headers)),
+            }
+
+            rows = match source_column {
+                Some(column) => {
+                    let column = column.to_string();
+                    let path = path.clone();
+                    Box::new(rows.chain(stream.rows.map(move |row| {
+                        let mut record = row?;
+                        record.insert(column.clone(), path.clone());
+                        Ok(record)
+                    })))
+                }
+                None => Box::new(rows.chain(stream.rows)),
+            };
+        }
+
+        let mut headers = first.map(|(_, headers)| headers).unwrap_or_default();
+        if let Some(column) = source_column
+            && !headers.iter().any(|h| h == column)
+        {
+            headers.push(column.to_string());
+        }
+
+        let mut stream = Self::from_parts(Vec::new(), headers, Vec::new());
+        stream.rows = rows;
+        Ok(stream.source(format!("from_files {}", paths.join(", "))))
+    }
+
+    /// Opens `path` as a workbook, JSON or CSV file, without recording a lineage step.
+    fn open(path: &str, dialect: &Dialect, sheet: &SheetOptions) -> Result<Self, Box<dyn Error>> {
+        if is_spreadsheet(path) {
+            let (headers, records) = read_sheet(path, sheet)?;
+            return Ok(Self::from_parts(records, headers, Vec::new()));
+        }
+        let format = FileFormat::from_path(path);
+        if format == FileFormat::Ndjson {
+            let (headers, rows) = ndjson_rows(path)?;
+            let mut stream = Self::from_parts(Vec::new(), headers, Vec::new());
+            stream.rows = rows;
+            return Ok(stream);
+        }
+        if format == FileFormat::Json {
+            let (headers, records) = read_json(path, format)?;
+            return Ok(Self::from_parts(records, headers, Vec::new()));
+        }
+
+        let mut rdr = dialect.reader(path)?;
+        let headers = rdr.headers()?.clone();
+        let header_names = headers.iter().map(str::to_string).collect();
+        let source: Arc<str> = Arc::from(path);
+
+        let rows = rdr.into_records().map(move |result| {
+            let record = result?;
+            Ok(record_from_row(&headers, &record, &source))
+        });
+
+        let mut stream = Self::from_parts(Vec::new(), header_names, Vec::new());
+        stream.rows = Box::new(rows);
+        Ok(stream)
+    }
+
+    /// Records reading the input as the first lineage step.
+    fn source(self, step: String) -> Self {
+        let step = self.track(step, &[]);
+        self.map_rows(step, |_| (), |_, record, _| Some(Ok(record)))
+    }
+
+    /// Sets how [`RecordStream::try_derive`] handles failing rows.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub(crate) fn with_error_log(mut self, errors: ErrorLog) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Writes rows that `to_file` skips to `path`; see [`Pipeline::rejects_to`].
+    pub fn rejects_to(mut self, path: &str) -> Self {
+        self.write_options.rejects_path = Some(path.to_string());
+        self
+    }
+
+    /// Makes `to_file` fail on the first incomplete row instead of skipping it.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.write_options.strict = strict;
+        self
+    }
+
+    /// Makes `to_file` write incomplete rows; see [`Pipeline::keep_incomplete`].
+    pub fn keep_incomplete(mut self, keep: bool) -> Self {
+        self.write_options.keep_incomplete = keep;
+        self
+    }
+
+    /// Sets the CSV dialect used by `to_file`.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Overrides the output format that `to_file` would pick from the extension.
+    pub fn format(mut self, format: FileFormat) -> Self {
+        self.write_options.format = Some(format);
+        self
+    }
+
+    /// Sets where `collect` and `to_file` report progress; see [`Pipeline::progress`].
+    pub fn progress(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.progress = sink;
+        self
+    }
+
+    /// Evaluates `filter`, `derive` and `try_derive` on the rayon pool, a chunk of rows at a
+    /// time. Row order and error order stay the same as a sequential run.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub(crate) fn with_write_options(mut self, write_options: WriteOptions) -> Self {
+        self.write_options = write_options;
+        self
+    }
+
+    pub(crate) fn with_lineage(mut self, lineage: Lineage) -> Self {
+        self.lineage = lineage;
+        self
+    }
+
+    /// Columns the rows will have once the steps set up so far have run.
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub fn passthrough(&self) -> &[String] {
+        &self.passthrough
+    }
+
+    /// Handle to the steps set up so far; their row counts and times fill in as the
+    /// stream is consumed.
+    pub fn lineage(&self) -> Lineage {
+        self.lineage.clone()
+    }
+
+    /// Adds a step to the lineage, comparing `headers_before` with the current headers.
+    fn track(&self, step: String, headers_before: &[String]) -> usize {
+        let (columns_added, columns_dropped) = column_changes(headers_before, &self.headers);
+        self.lineage.push(StepRecord {
+            step,
+            columns_added,
+            columns_dropped,
+            ..StepRecord::default()
+        })
+    }
+
+    /// Handle to the row errors collected so far; keep it to report after `to_file`.
+    pub fn error_log(&self) -> ErrorLog {
+        self.errors.clone()
+    }
+
+    /// Runs a row step in two phases: `evaluate` looks at the row and may run on the rayon
+    /// pool, `apply` then consumes the row and its result in stream order and may drop it.
+    ///
+    /// Rows in and out and the time spent are added to lineage step `step`.
+    fn map_rows<T, E, A>(mut self, step: usize, evaluate: E, mut apply: A) -> Self
+    where
+        T: Send + 'a,
+        E: Fn(&Record) -> T + Sync + 'a,
+        A: FnMut(usize, Record, T) -> Option<RowResult> + 'a,
+    {
+        let rows = std::mem::replace(&mut self.rows, Box::new(std::iter::empty())).enumerate();
+        let lineage = self.lineage.clone();
+        let record_rows = move |rows_in: usize, rows_out: usize, started: Instant| {
+            lineage.update(step, |step| {
+                step.rows_in += rows_in;
+                step.rows_out += rows_out;
+                step.duration_ms += millis(started.elapsed());
+            });
+        };
+
+        self.rows = if self.parallel {
+            Box::new(
+                Chunks::new(rows, CHUNK_SIZE).flat_map(move |chunk| match chunk {
+                    Ok(chunk) => {
+                        let started = Instant::now();
+                        let rows_in = chunk.len();
+                        let values: Vec<T> = chunk
+                            .par_iter()
+                            .map(|(_, record)| evaluate(record))
+                            .collect();
+                        let rows: Vec<RowResult> = chunk
+                            .into_iter()
+                            .zip(values)
+                            .filter_map(|((idx, record), value)| apply(idx, record, value))
+                            .collect();
+                        let rows_out = rows.iter().filter(|row| row.is_ok()).count();
+                        record_rows(rows_in, rows_out, started);
+                        rows
+                    }
+                    Err(err) => vec![Err(err)],
+                }),
+            )
+        } else {
+            Box::new(rows.filter_map(move |(idx, row)| match row {
+                Ok(record) => {
+                    let started = Instant::now();
+                    let value = evaluate(&record);
+                    let row = apply(idx, record, value);
+                    let rows_out = usize::from(matches!(row, Some(Ok(_))));
+                    record_rows(1, rows_out, started);
+                    row
+                }
+                Err(err) => Some(Err(err)),
+            }))
+        };
+        self
+    }
+
+    pub fn filter<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&Record) -> bool + Sync + 'a,
+    {
+        let step = self.track("filter".to_string(), &self.headers);
+        self.map_rows(step, predicate, |_, record, keep| {
+            keep.then_some(Ok(record))
+        })
+    }
+
+    pub fn derive<F>(mut self, header: &str, transform: F) -> Self
+    where
+        F: Fn(&Record) -> String + Sync + 'a,
+    {
+        let headers_before = self.headers.clone();
+        if !self.headers.iter().any(|h| h == header) {
+            self.headers.push(header.to_string());
+        }
+        let step = self.track(format!("derive {}", header), &headers_before);
+
+        let header = header.to_string();
+        self.map_rows(step, transform, move |_, mut record, new_value| {
+            record.insert(header.clone(), new_value);
+            Some(Ok(record))
+        })
+    }
+
+    /// Streaming counterpart of [`Pipeline::try_derive`]. Under [`ErrorPolicy::Abort`] the
+    /// first failing row ends the stream with its [`RowError`].
+    pub fn try_derive<F>(mut self, header: &str, transform: F) -> Self
+    where
+        F: Fn(&Record) -> Result<String, String> + Sync + 'a,
+    {
+        let headers_before = self.headers.clone();
+        if !self.headers.iter().any(|h| h == header) {
+            self.headers.push(header.to_string());
+        }
+        let step = self.track(format!("try_derive {}", header), &headers_before);
+
+        let header = header.to_string();
+        let policy = self.policy;
+        let errors = self.errors.clone();
+        self.map_rows(step, transform, move |idx, mut record, result| {
+            let new_value = match result {
+                Ok(value) => value,
+                Err(message) => {
+                    let error = RowError::new(&record, idx, &header, message);
+                    if policy == ErrorPolicy::Abort {
+                        return Some(Err(error.into()));
+                    }
+                    errors.push(error);
+                    String::new()
+                }
+            };
+            record.insert(header.clone(), new_value);
+            Some(Ok(record))
+        })
+    }
+
+    /// Streaming counterpart of [`Pipeline::validate`]. Missing required columns are in
+    /// the report at once; row problems fill in as the stream is consumed, and under
+    /// [`ErrorPolicy::Abort`] the first one ends the stream.
+    pub fn validate(self, schema: &Schema) -> (Self, ValidationReport) {
+        let step = self.track(format!("validate {}", schema.name), &self.headers);
+        let mut validator = Validator::new(schema, &self.headers);
+        let report = validator.report();
+
+        let policy = self.policy;
+        let stream = self.map_rows(
+            step,
+            |_| (),
+            move |idx, record, _| match validator.check(idx, &record) {
+                Some(error) if policy == ErrorPolicy::Abort => Some(Err(error.into())),
+                _ => Some(Ok(record)),
+            },
+        );
+        (stream, report)
+    }
+
+    pub fn select(self, headers: Vec<&str>) -> Self {
+        self.select_columns(headers, false)
+    }
+
+    /// Streaming counterpart of [`Pipeline::select_with_passthrough`].
+    pub fn select_with_passthrough(self, headers: Vec<&str>) -> Self {
+        self.select_columns(headers, true)
+    }
+
+    fn select_columns(mut self, headers: Vec<&str>, passthrough: bool) -> Self {
+        let rest = if passthrough {
+            passthrough_of(&self.headers, &headers)
+        } else {
+            Vec::new()
+        };
+        let mut selected: Vec<String> = headers.into_iter().map(str::to_string).collect();
+
+        selected.extend(rest.iter().cloned());
+
+        let headers_before = std::mem::replace(&mut self.headers, selected.clone());
+        self.passthrough = rest;
+        let step = self.track(
+            if passthrough {
+                "select_with_passthrough"
+            } else {
+                "select"
+            }
+            .to_string(),
+            &headers_before,
+        );
+
+        self.map_rows(
+            step,
+            |_| (),
+            move |_, mut record, _| {
+                let mut new_record = Record::with_capacity(selected.len());
+                new_record.set_position(record.position().cloned());
+                for header in &selected {
+                    let value = record.remove(header).unwrap_or_default();
+                    new_record.insert(header.clone(), value);
+                }
+                Some(Ok(new_record))
+            },
+        )
+    }
+
+    /// Drains the stream into an in-memory [`Pipeline`].
+    pub fn collect(self) -> Result<Pipeline, Box<dyn Error>> {
+        let pb = self.progress.start("Collecting records...", None);
+
+        let mut records = Vec::new();
+        for row in self.rows {
+            records.push(row?);
+            pb.inc(1);
+        }
+
+        pb.finish(&format!("Collected {} records", records.len()));
+        let mut pipeline = Pipeline::from_records(records);
+        pipeline.headers = self.headers;
+        pipeline.passthrough = self.passthrough;
+        pipeline.policy = self.policy;
+        pipeline.errors = self.errors;
+        pipeline.write_options = self.write_options;
+        pipeline.dialect = self.dialect;
+        pipeline.progress = self.progress;
+        pipeline.parallel = self.parallel;
+        pipeline.lineage = self.lineage;
+        Ok(pipeline)
+    }
+
+    pub fn to_file(self, path: &str, headers: Vec<&str>) -> Result<(), Box<dyn Error>> {
+        let mut writer = OutputWriter::create(
+            path,
+            &headers,
+            &self.passthrough,
+            &self.write_options,
+            &self.dialect,
+            &self.errors,
+        )?;
+
+        let pb = self
+            .progress
+            .start(&format!("Streaming to {}...", path), None);
+
+        let started = Instant::now();
+        let mut rows_in = 0;
+        for (idx, row) in self.rows.enumerate() {
+            rows_in += 1;
+            if let Err(err) = row.and_then(|record| writer.write(&record, idx)) {
+                pb.abandon(&format!("Failed streaming to {}", path));
+                return Err(err);
+            }
+            pb.inc(1);
+        }
+
+        pb.finish(&finish_message(
+            "streaming",
+            path,
+            &writer,
+            &self.write_options,
+        ));
+        let rows_out = writer.written;
+        writer.finish()?;
+        let (columns_added, columns_dropped) =
+            column_changes(&self.headers, &output_row(&headers, &self.passthrough));
+        self.lineage.push(StepRecord {
+            step: format!("to_file {}", path),
+            rows_in,
+            rows_out,
+            columns_added,
+            columns_dropped,
+            duration_ms: millis(started.elapsed()),
+        });
+        Ok(())
+    }
+}
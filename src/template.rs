@@ -0,0 +1,417 @@
+use crate::{Pipeline, Record};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A string template for derived columns, e.g. `{campus}-{Course Code|nospace|upper}`.
+///
+/// - `{Column}` inserts a column of the row, or a value bound with [`Template::bind`]
+///   when the row has no such column. `{'text'}` inserts the quoted text.
+/// - Filters follow a `|`: `upper`, `lower`, `trim`, `nospace`, `first:N`, `last:N`,
+///   `pad:N` (left-pads with zeros), `replace:'from':'to'` and `default:'text'`.
+/// - `{if Column}...{else}...{end}` picks a branch on whether the value is non-blank;
+///   `{if Column == 'text'}` and `!=` compare it instead. `{else}` is optional.
+/// - `{{` and `}}` are literal braces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+    values: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Expr(Expr),
+    If {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Expr {
+    source: Source,
+    filters: Vec<Filter>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Source {
+    Column(String),
+    Literal(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Filter {
+    Upper,
+    Lower,
+    Trim,
+    NoSpace,
+    First(usize),
+    Last(usize),
+    Pad(usize),
+    Replace(String, String),
+    Default(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Condition {
+    NotBlank(Expr),
+    Equals(Expr, String),
+    NotEquals(Expr, String),
+}
+
+/// An `{if}` whose `{end}` has not been reached yet.
+struct OpenIf {
+    condition: Condition,
+    /// Nodes before the `{if}`.
+    before: Vec<Node>,
+    /// The then-branch, once `{else}` was seen.
+    then: Option<Vec<Node>>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, Box<dyn Error>> {
+        let error = |message: String| -> Box<dyn Error> {
+            format!("{} in template '{}'", message, template).into()
+        };
+
+        let mut open: Vec<OpenIf> = Vec::new();
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                text.push(c);
+                rest = &rest[2..];
+                continue;
+            }
+            if c == '}' {
+                return Err(error("unmatched '}'".to_string()));
+            }
+            if c != '{' {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+
+            let close = tag_end(rest).ok_or_else(|| error("unclosed '{'".to_string()))?;
+            let tag = rest[1..close].trim();
+            rest = &rest[close + 1..];
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+
+            if let Some(condition) = tag.strip_prefix("if ") {
+                open.push(OpenIf {
+                    condition: parse_condition(condition).map_err(error)?,
+                    before: std::mem::take(&mut nodes),
+                    then: None,
+                });
+            } else if tag == "else" {
+                let current = open
+                    .last_mut()
+                    .filter(|current| current.then.is_none())
+                    .ok_or_else(|| error("'{else}' without '{if}'".to_string()))?;
+                current.then = Some(std::mem::take(&mut nodes));
+            } else if tag == "end" {
+                let current = open
+                    .pop()
+                    .ok_or_else(|| error("'{end}' without '{if}'".to_string()))?;
+                let branch = std::mem::replace(&mut nodes, current.before);
+                let (then, otherwise) = match current.then {
+                    Some(then) => (then, branch),
+                    None => (branch, Vec::new()),
+                };
+                nodes.push(Node::If {
+                    condition: current.condition,
+                    then,
+                    otherwise,
+                });
+            } else {
+                nodes.push(Node::Expr(parse_expr(tag).map_err(error)?));
+            }
+        }
+
+        if !open.is_empty() {
+            return Err(error("'{if}' without '{end}'".to_string()));
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok(Self {
+            nodes,
+            values: HashMap::new(),
+        })
+    }
+
+    /// Binds `{name}` to `value` for rows without a `name` column, e.g. the campus.
+    pub fn bind(mut self, name: &str, value: &str) -> Self {
+        self.values.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Columns the template refers to that are not bound values, in order of appearance.
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        collect_columns(&self.nodes, &mut columns);
+        columns.retain(|column| !self.values.contains_key(*column));
+        columns
+    }
+
+    /// Fails on the first referenced column missing from `headers`.
+    pub fn check_columns(&self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        match self
+            .columns()
+            .into_iter()
+            .find(|column| !headers.iter().any(|header| header == column))
+        {
+            Some(column) => Err(format!("template refers to unknown column '{}'", column).into()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn render(&self, record: &Record) -> String {
+        self.render_escaped(record, &|value| value)
+    }
+
+    /// Like [`Template::render`], but passes every inserted value through `escape`, such as
+    /// to keep a value from adding path separators.
+    pub(crate) fn render_escaped(
+        &self,
+        record: &Record,
+        escape: &dyn Fn(String) -> String,
+    ) -> String {
+        let mut output = String::new();
+        self.render_nodes(&self.nodes, record, escape, &mut output);
+        output
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        record: &Record,
+        escape: &dyn Fn(String) -> String,
+        output: &mut String,
+    ) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Expr(expr) => output.push_str(&escape(self.evaluate(expr, record))),
+                Node::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let holds = match condition {
+                        Condition::NotBlank(expr) => !self.evaluate(expr, record).trim().is_empty(),
+                        Condition::Equals(expr, value) => self.evaluate(expr, record) == *value,
+                        Condition::NotEquals(expr, value) => self.evaluate(expr, record) != *value,
+                    };
+                    let branch = if holds { then } else { otherwise };
+                    self.render_nodes(branch, record, escape, output);
+                }
+            }
+        }
+    }
+
+    fn evaluate(&self, expr: &Expr, record: &Record) -> String {
+        let mut value = match &expr.source {
+            Source::Literal(text) => text.clone(),
+            Source::Column(column) => record
+                .get(column)
+                .or_else(|| self.values.get(column))
+                .cloned()
+                .unwrap_or_default(),
+        };
+        for filter in &expr.filters {
+            value = filter.apply(value);
+        }
+        value
+    }
+}
+
+impl Filter {
+    fn apply(&self, value: String) -> String {
+        match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Trim => value.trim().to_string(),
+            Filter::NoSpace => value.chars().filter(|c| !c.is_whitespace()).collect(),
+            Filter::First(count) => value.chars().take(*count).collect(),
+            Filter::Last(count) => {
+                let skip = value.chars().count().saturating_sub(*count);
+                value.chars().skip(skip).collect()
+            }
+            Filter::Pad(width) => format!("{:0>width$}", value, width = *width),
+            Filter::Replace(from, to) if !from.is_empty() => value.replace(from.as_str(), to),
+            Filter::Replace(..) => value,
+            Filter::Default(default) if value.trim().is_empty() => default.clone(),
+            Filter::Default(_) => value,
+        }
+    }
+}
+
+fn collect_columns<'a>(nodes: &'a [Node], columns: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Expr(expr) => push_column(expr, columns),
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                match condition {
+                    Condition::NotBlank(expr)
+                    | Condition::Equals(expr, _)
+                    | Condition::NotEquals(expr, _) => push_column(expr, columns),
+                }
+                collect_columns(then, columns);
+                collect_columns(otherwise, columns);
+            }
+        }
+    }
+}
+
+fn push_column<'a>(expr: &'a Expr, columns: &mut Vec<&'a str>) {
+    if let Source::Column(column) = &expr.source
+        && !columns.contains(&column.as_str())
+    {
+        columns.push(column);
+    }
+}
+
+impl Pipeline {
+    /// Derives `header` by rendering `template` for every row; see [`Template`] for the
+    /// syntax. Fails before running if the template refers to an unknown column.
+    pub fn derive_template(
+        self,
+        header: &str,
+        template: &Template,
+    ) -> Result<Self, Box<dyn Error>> {
+        template.check_columns(&self.headers)?;
+        Ok(self.derive(header, |record| template.render(record)))
+    }
+}
+
+/// Byte index of the `}` closing the tag `rest` starts with, skipping quoted text.
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (idx, c) in rest.char_indices().skip(1) {
+        match c {
+            '\'' => quoted = !quoted,
+            '}' if !quoted => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits `text` on `separator` outside quoted text.
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (idx, c) in text.char_indices() {
+        if c == '\'' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&text[start..idx]);
+            start = idx + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// The text of `'quoted'`, or `None` if `text` is not quoted.
+fn unquote(text: &str) -> Option<&str> {
+    let text = text.trim();
+    text.strip_prefix('\'')?.strip_suffix('\'')
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let mut parts = split_unquoted(text, '|').into_iter();
+    let source = parts.next().unwrap_or_default().trim();
+    let source = match unquote(source) {
+        Some(literal) => Source::Literal(literal.to_string()),
+        None if source.is_empty() => return Err("empty '{}'".to_string()),
+        None => Source::Column(source.to_string()),
+    };
+    let filters = parts.map(parse_filter).collect::<Result<_, _>>()?;
+    Ok(Expr { source, filters })
+}
+
+fn parse_filter(text: &str) -> Result<Filter, String> {
+    let parts = split_unquoted(text, ':');
+    let name = parts[0].trim();
+    let args = &parts[1..];
+
+    let count = || -> Result<usize, String> {
+        match args {
+            [count] => count
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' expects a number, got '{}'", name, count.trim())),
+            _ => Err(format!("'{}' expects one number", name)),
+        }
+    };
+    let text_arg = |idx: usize| -> Result<String, String> {
+        args.get(idx)
+            .and_then(|arg| unquote(arg))
+            .map(str::to_string)
+            .ok_or_else(|| format!("'{}' expects quoted text arguments", name))
+    };
+
+    let filter = match (name, args.len()) {
+        ("upper", 0) => Filter::Upper,
+        ("lower", 0) => Filter::Lower,
+        ("trim", 0) => Filter::Trim,
+        ("nospace", 0) => Filter::NoSpace,
+        ("first", _) => Filter::First(count()?),
+        ("last", _) => Filter::Last(count()?),
+        ("pad", _) => Filter::Pad(count()?),
+        ("replace", 2) => Filter::Replace(text_arg(0)?, text_arg(1)?),
+        ("default", 1) => Filter::Default(text_arg(0)?),
+        ("upper" | "lower" | "trim" | "nospace", _) => {
+            return Err(format!("'{}' takes no arguments", name));
+        }
+        ("replace" | "default", _) => {
+            return Err(format!("wrong number of arguments to '{}'", name));
+        }
+        _ => return Err(format!("unknown filter '{}'", name)),
+    };
+    Ok(filter)
+}
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    for operator in ["==", "!="] {
+        let Some(idx) = find_unquoted(text, operator) else {
+            continue;
+        };
+        let expr = parse_expr(&text[..idx])?;
+        let value = unquote(&text[idx + operator.len()..])
+            .ok_or_else(|| format!("'{}' compares with quoted text", operator))?
+            .to_string();
+        return Ok(if operator == "==" {
+            Condition::Equals(expr, value)
+        } else {
+            Condition::NotEquals(expr, value)
+        });
+    }
+    Ok(Condition::NotBlank(parse_expr(text)?))
+}
+
+/// Byte index of the first `pattern` in `text` outside quoted text.
+fn find_unquoted(text: &str, pattern: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (idx, c) in text.char_indices() {
+        if c == '\'' {
+            quoted = !quoted;
+        } else if !quoted && text[idx..].starts_with(pattern) {
+            return Some(idx);
+        }
+    }
+    None
+}
@@ -0,0 +1,285 @@
+use crate::lineage::StepTimer;
+use crate::{ErrorLog, ErrorPolicy, Lineage, Pipeline, Position, Record, RowError, headers_of};
+use csv::StringRecord;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::error::Error;
+
+/// Rows deserialized into `T`, so a misspelled or missing column fails when the rows are
+/// read instead of turning into blank output.
+///
+/// Get one from [`Pipeline::typed`] or [`TypedPipeline::from_rows`], and go back to
+/// untyped rows with [`TypedPipeline::into_pipeline`]. Settings, the error log and the
+/// lineage carry over both ways.
+pub struct TypedPipeline<T> {
+    /// Each row with where it was read from, for row errors.
+    rows: Vec<(Option<Position>, T)>,
+    /// Rows left out after an error, kept so `to_file` sends them to the rejects file.
+    rejected: Vec<Record>,
+    /// Holds the settings, error log and lineage; its records stay empty.
+    base: Pipeline,
+}
+
+impl Pipeline {
+    /// Deserializes every row into `T` by column name, parsing numbers and booleans.
+    ///
+    /// Columns `T` does not name are dropped. Under [`ErrorPolicy::Abort`] the first row
+    /// that does not fit fails the step; otherwise it is logged and left out.
+    pub fn typed<T: DeserializeOwned>(mut self) -> Result<TypedPipeline<T>, Box<dyn Error>> {
+        let step = self.begin_step(format!("typed {}", type_name::<T>()));
+        let pb = self.progress.start(
+            &format!("Reading {} rows...", type_name::<T>()),
+            Some(self.records.len() as u64),
+        );
+
+        let headers = StringRecord::from(self.headers.clone());
+        let mut rows = Vec::with_capacity(self.records.len());
+        let mut rejected = Vec::new();
+        for (idx, record) in std::mem::take(&mut self.records).into_iter().enumerate() {
+            let values: StringRecord = self
+                .headers
+                .iter()
+                .map(|header| record.get(header).map(String::as_str).unwrap_or(""))
+                .collect();
+            match values.deserialize::<T>(Some(&headers)) {
+                Ok(row) => rows.push((record.position().cloned(), row)),
+                Err(err) => {
+                    let (column, message) = match err.into_kind() {
+                        csv::ErrorKind::Deserialize { err, .. } => {
+                            match err
+                                .field()
+                                .and_then(|field| self.headers.get(field as usize))
+                            {
+                                Some(column) => {
+                                    let value = record.get(column).map(String::as_str);
+                                    let message =
+                                        format!("'{}': {}", value.unwrap_or(""), err.kind());
+                                    (column.clone(), message)
+                                }
+                                None => (type_name::<T>().to_string(), err.kind().to_string()),
+                            }
+                        }
+                        kind => (type_name::<T>().to_string(), format!("{:?}", kind)),
+                    };
+                    let error = RowError::new(&record, idx, &column, message);
+                    if self.policy == ErrorPolicy::Abort {
+                        pb.abandon(&format!("Failed reading {} rows", type_name::<T>()));
+                        return Err(error.into());
+                    }
+                    self.errors.push(error);
+                    rejected.push(record);
+                }
+            }
+            pb.inc(1);
+        }
+
+        pb.finish(&format!("Read {} {} rows", rows.len(), type_name::<T>()));
+        let lineage = self.lineage.clone();
+        step.finish(&lineage, rows.len(), &self.headers);
+        Ok(TypedPipeline {
+            rows,
+            rejected,
+            base: self,
+        })
+    }
+
+    /// Starts typed rows that keep this pipeline's settings, such as its error policy and
+    /// progress sink. The pipeline's own records are dropped.
+    pub fn with_rows<T>(mut self, rows: Vec<T>) -> TypedPipeline<T> {
+        self.records = Vec::new();
+        TypedPipeline {
+            rows: rows.into_iter().map(|row| (None, row)).collect(),
+            rejected: Vec::new(),
+            base: self,
+        }
+    }
+}
+
+impl<T> TypedPipeline<T> {
+    pub fn from_rows(rows: Vec<T>) -> Self {
+        Pipeline::new().with_rows(rows)
+    }
+
+    /// Handle to the row errors collected so far; see [`Pipeline::error_log`].
+    pub fn error_log(&self) -> ErrorLog {
+        self.base.error_log()
+    }
+
+    /// Handle to the steps run so far; see [`Pipeline::lineage`].
+    pub fn lineage(&self) -> Lineage {
+        self.base.lineage()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &T> {
+        self.rows.iter().map(|(_, row)| row)
+    }
+
+    pub fn into_rows(self) -> Vec<T> {
+        self.rows.into_iter().map(|(_, row)| row).collect()
+    }
+
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool,
+    {
+        let step = self.begin_step("filter");
+        self.rows.retain(|(_, row)| predicate(row));
+        self.end_step(step);
+        self
+    }
+
+    /// Stable sort, keeping rows that compare equal in their current order.
+    pub fn sort_by<F>(mut self, mut compare: F) -> Self
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let step = self.begin_step("sort_by");
+        self.rows.sort_by(|(_, a), (_, b)| compare(a, b));
+        self.end_step(step);
+        self
+    }
+
+    pub fn map<U, F>(self, transform: F) -> TypedPipeline<U>
+    where
+        F: Fn(T) -> U,
+    {
+        self.flat_map(|row| Some(transform(row)))
+    }
+
+    /// Replaces every row with any number of rows, such as one enrolment per course.
+    pub fn flat_map<U, I, F>(self, transform: F) -> TypedPipeline<U>
+    where
+        I: IntoIterator<Item = U>,
+        F: Fn(T) -> I,
+    {
+        let step = self.begin_step(format!("map {}", type_name::<U>()));
+        let rows = self
+            .rows
+            .into_iter()
+            .flat_map(|(position, row)| {
+                transform(row)
+                    .into_iter()
+                    .map(move |row| (position.clone(), row))
+            })
+            .collect();
+        let typed = TypedPipeline {
+            rows,
+            rejected: self.rejected,
+            base: self.base,
+        };
+        typed.end_step(step);
+        typed
+    }
+
+    /// Like [`TypedPipeline::map`] for conversions that can fail. A failing row is
+    /// handled like in [`Pipeline::try_derive`], except that it is left out rather than
+    /// blanked; `to_file` then writes it to the rejects file with blank values.
+    pub fn try_map<U, F>(mut self, transform: F) -> Result<TypedPipeline<U>, Box<dyn Error>>
+    where
+        F: Fn(T) -> Result<U, String>,
+    {
+        let step = self.begin_step(format!("try_map {}", type_name::<U>()));
+        let mut rows = Vec::with_capacity(self.rows.len());
+        for (idx, (position, row)) in self.rows.into_iter().enumerate() {
+            match transform(row) {
+                Ok(row) => rows.push((position, row)),
+                Err(message) => {
+                    let error = RowError {
+                        path: position.as_ref().map(|p| p.path.to_string()),
+                        line: position.as_ref().map(|p| p.line),
+                        record: idx + 1,
+                        column: type_name::<U>().to_string(),
+                        message,
+                    };
+                    if self.base.policy == ErrorPolicy::Abort {
+                        return Err(error.into());
+                    }
+                    self.base.errors.push(error);
+                    let mut record = Record::new();
+                    record.set_position(position);
+                    self.rejected.push(record);
+                }
+            }
+        }
+        let typed = TypedPipeline {
+            rows,
+            rejected: self.rejected,
+            base: self.base,
+        };
+        typed.end_step(step);
+        Ok(typed)
+    }
+
+    fn begin_step(&self, step: impl Into<String>) -> StepTimer {
+        StepTimer::start(step.into(), self.rows.len(), &self.base.headers)
+    }
+
+    fn end_step(&self, timer: StepTimer) {
+        timer.finish(&self.base.lineage, self.rows.len(), &self.base.headers);
+    }
+}
+
+impl<T: Serialize> TypedPipeline<T> {
+    /// Serializes the rows back into records, one column per field in declaration order.
+    /// Fields holding `None` become blank; nested structs and lists are rejected.
+    ///
+    /// Rows left out after an error follow the others with none of these columns filled in,
+    /// so `to_file` skips them or sends them to the rejects file.
+    pub fn into_pipeline(self) -> Result<Pipeline, Box<dyn Error>> {
+        let step = self.begin_step("into_pipeline");
+        let mut records = Vec::with_capacity(self.rows.len());
+        for (position, row) in &self.rows {
+            let Value::Object(fields) = serde_json::to_value(row)? else {
+                return Err(format!("{} does not serialize to columns", type_name::<T>()).into());
+            };
+            let mut record = Record::with_capacity(fields.len());
+            for (header, value) in fields {
+                let value = match value {
+                    Value::Null => String::new(),
+                    Value::String(value) => value,
+                    Value::Bool(_) | Value::Number(_) => value.to_string(),
+                    Value::Array(_) | Value::Object(_) => {
+                        return Err(format!(
+                            "{}: field '{}' is not a single value",
+                            type_name::<T>(),
+                            header
+                        )
+                        .into());
+                    }
+                };
+                record.insert(header, value);
+            }
+            record.set_position(position.clone());
+            records.push(record);
+        }
+
+        let mut pipeline = self.base;
+        pipeline.headers = headers_of(&records);
+        records.extend(self.rejected);
+        pipeline.passthrough = Vec::new();
+        pipeline.records = records;
+        step.finish(&pipeline.lineage, pipeline.records.len(), &pipeline.headers);
+        Ok(pipeline)
+    }
+
+    /// Writes `headers` of every row; see [`Pipeline::to_file`].
+    pub fn to_file(self, path: &str, headers: Vec<&str>) -> Result<(), Box<dyn Error>> {
+        self.into_pipeline()?.to_file(path, headers)
+    }
+}
+
+/// `T` without its module path, such as `CurriculumRow`.
+fn type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
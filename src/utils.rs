@@ -2,64 +2,64 @@ use crate::constants::*;
 use chrono::Local;
 use fake::Fake;
 use fake::faker::name::en::{FirstName, LastName};
-use moodle_course_builder::Record;
 use rand::Rng;
 use rand::rngs::OsRng;
 
 pub fn get_category_path(
-    row: &Record,
+    program: &str,
+    semester: &str,
     campus_upper: &str,
     dept_upper: &str,
     start_year_short: &str,
     end_year_short: &str,
-) -> String {
-    let program = row
-        .get(HEADER_PROGRAM)
-        .map(|s| s.as_str())
-        .unwrap_or(DEFAULT_PROGRAM);
-    let semester = row.get(HEADER_SEMESTER).map(|s| s.as_str()).unwrap_or("");
+) -> Result<String, String> {
     let semester_tag = format!("S{}{}{}", semester, start_year_short, end_year_short);
     if !KNOWN_SEMESTER_TAGS.contains(&semester_tag.as_str()) {
-        return String::new();
+        return Err(format!("unknown semester tag {}", semester_tag));
     }
-    format!(
+    Ok(format!(
         "{} / {} / {} / {}",
         campus_upper, semester_tag, dept_upper, program
-    )
+    ))
 }
 
-pub fn get_course_start_date(row: &Record, start_year_full: &str, end_year_full: &str) -> String {
-    let semester = row.get(HEADER_SEMESTER).map(|s| s.as_str()).unwrap_or("");
+pub fn get_course_start_date(
+    semester: &str,
+    start_year_full: &str,
+    end_year_full: &str,
+) -> Result<String, String> {
     match semester {
-        SEMESTER_1 => format!("{}-{}", start_year_full, DATE_SEM1_START),
-        SEMESTER_2 => format!("{}-{}", end_year_full, DATE_SEM2_START),
-        _ => String::new(),
+        SEMESTER_1 => Ok(format!("{}-{}", start_year_full, DATE_SEM1_START)),
+        SEMESTER_2 => Ok(format!("{}-{}", end_year_full, DATE_SEM2_START)),
+        _ => Err(format!("no start date for semester '{}'", semester)),
     }
 }
 
-pub fn get_course_end_date(row: &Record, start_year_full: &str, end_year_full: &str) -> String {
-    let semester = row.get(HEADER_SEMESTER).map(|s| s.as_str()).unwrap_or("");
+pub fn get_course_end_date(
+    semester: &str,
+    start_year_full: &str,
+    end_year_full: &str,
+) -> Result<String, String> {
     match semester {
-        SEMESTER_1 => format!("{}-{}", start_year_full, DATE_SEM1_END),
-        SEMESTER_2 => format!("{}-{}", end_year_full, DATE_SEM2_END),
-        _ => String::new(),
+        SEMESTER_1 => Ok(format!("{}-{}", start_year_full, DATE_SEM1_END)),
+        SEMESTER_2 => Ok(format!("{}-{}", end_year_full, DATE_SEM2_END)),
+        _ => Err(format!("no end date for semester '{}'", semester)),
     }
 }
 
 pub fn get_short_name(
-    row: &Record,
+    course_code: &str,
+    semester: &str,
     campus_upper: &str,
     start_year_short: &str,
     end_year_short: &str,
 ) -> String {
-    let semester = row.get(HEADER_SEMESTER).map(|s| s.as_str()).unwrap_or("");
     let semester_tag = format!("S{}{}{}", semester, start_year_short, end_year_short);
-    let course_code = row
-        .get(HEADER_COURSE_CODE)
-        .map(|s| s.as_str())
-        .unwrap_or("")
-        .replace(' ', "");
-    let course_code_with_edp = format!("{}-{}", course_code, generate_random_edp_code());
+    let course_code_with_edp = format!(
+        "{}-{}",
+        course_code.replace(' ', ""),
+        generate_random_edp_code()
+    );
     format!("{}-{}-{}", campus_upper, semester_tag, course_code_with_edp)
 }
 
@@ -0,0 +1,240 @@
+use crate::format::JsonWriter;
+use crate::{Dialect, ErrorLog, FileFormat, Record, RowError};
+use std::error::Error;
+use std::fs::File;
+
+/// Header of the extra column in the rejects file explaining why a row was not written.
+pub const REJECT_REASON_HEADER: &str = "reject_reason";
+
+/// How `to_file` treats rows with a blank value in one of the written headers.
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// Companion CSV receiving every skipped row plus a [`REJECT_REASON_HEADER`] column.
+    pub rejects_path: Option<String>,
+    /// Fail on the first incomplete row instead of skipping it.
+    pub strict: bool,
+    /// Output format; picked from the file extension when `None`.
+    pub format: Option<FileFormat>,
+    /// Write incomplete rows like any other, for files meant for review.
+    pub keep_incomplete: bool,
+}
+
+/// The written header row: `headers` followed by the pass-through columns.
+pub(crate) fn output_row(headers: &[&str], passthrough: &[String]) -> Vec<String> {
+    headers
+        .iter()
+        .map(|header| header.to_string())
+        .chain(
+            passthrough
+                .iter()
+                .filter(|header| !headers.contains(&header.as_str()))
+                .cloned(),
+        )
+        .collect()
+}
+
+/// Headers from `headers` that are missing or blank in `record`.
+fn missing_headers<'h>(record: &Record, headers: &[&'h str]) -> Vec<&'h str> {
+    headers
+        .iter()
+        .copied()
+        .filter(|&header| {
+            record
+                .get(header)
+                .is_none_or(|value| value.trim().is_empty())
+        })
+        .collect()
+}
+
+/// A file written under a temporary name next to `path`, so a failed run leaves no partial
+/// output behind. Dropping it before [`PendingFile::commit`] removes the temporary file.
+struct PendingFile {
+    path: String,
+    temp: String,
+    committed: bool,
+}
+
+impl PendingFile {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            temp: format!("{}.tmp", path),
+            committed: false,
+        }
+    }
+
+    /// Moves the finished file to its real path.
+    fn commit(mut self) -> Result<(), Box<dyn Error>> {
+        std::fs::rename(&self.temp, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// One output file in either CSV or JSON layout.
+enum SinkWriter {
+    Csv(Box<csv::Writer<File>>),
+    Json(JsonWriter),
+}
+
+struct RowSink {
+    wtr: SinkWriter,
+    file: PendingFile,
+}
+
+impl RowSink {
+    fn create(
+        path: &str,
+        format: FileFormat,
+        columns: Vec<String>,
+        dialect: &Dialect,
+    ) -> Result<Self, Box<dyn Error>> {
+        let file = PendingFile::new(path);
+        let wtr = match format {
+            FileFormat::Csv => {
+                let mut wtr = dialect.writer(&file.temp)?;
+                wtr.write_record(&columns)?;
+                SinkWriter::Csv(Box::new(wtr))
+            }
+            FileFormat::Json | FileFormat::Ndjson => {
+                if let Some(parent) = std::path::Path::new(path).parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let out = File::create(&file.temp)?;
+                let array = format == FileFormat::Json;
+                SinkWriter::Json(JsonWriter::create(out, columns, array)?)
+            }
+        };
+        Ok(Self { wtr, file })
+    }
+
+    fn write_row<I>(&mut self, values: I) -> Result<(), Box<dyn Error>>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        match &mut self.wtr {
+            SinkWriter::Csv(wtr) => Ok(wtr.write_record(values)?),
+            SinkWriter::Json(wtr) => wtr.write_row(values),
+        }
+    }
+
+    /// Flushes the file, which still has to be committed to its real path.
+    fn finish(self) -> Result<PendingFile, Box<dyn Error>> {
+        match self.wtr {
+            SinkWriter::Csv(mut wtr) => wtr.flush()?,
+            SinkWriter::Json(wtr) => wtr.finish()?,
+        }
+        Ok(self.file)
+    }
+}
+
+/// Writes the output file of a pipeline, routing incomplete rows to the rejects file.
+///
+/// The rejects file always picks its format from its own extension.
+pub(crate) struct OutputWriter<'h> {
+    wtr: RowSink,
+    rejects: Option<RowSink>,
+    headers: &'h [&'h str],
+    columns: Vec<String>,
+    strict: bool,
+    keep_incomplete: bool,
+    errors: ErrorLog,
+    pub written: usize,
+    pub skipped: usize,
+}
+
+impl<'h> OutputWriter<'h> {
+    pub fn create(
+        path: &str,
+        headers: &'h [&'h str],
+        passthrough: &[String],
+        options: &WriteOptions,
+        dialect: &Dialect,
+        errors: &ErrorLog,
+    ) -> Result<Self, Box<dyn Error>> {
+        let columns = output_row(headers, passthrough);
+
+        let format = options
+            .format
+            .unwrap_or_else(|| FileFormat::from_path(path));
+        let wtr = RowSink::create(path, format, columns.clone(), dialect)?;
+
+        let rejects = match &options.rejects_path {
+            Some(rejects_path) => {
+                let mut reject_columns = columns.clone();
+                reject_columns.push(REJECT_REASON_HEADER.to_string());
+                Some(RowSink::create(
+                    rejects_path,
+                    FileFormat::from_path(rejects_path),
+                    reject_columns,
+                    dialect,
+                )?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            wtr,
+            rejects,
+            headers,
+            columns,
+            strict: options.strict,
+            keep_incomplete: options.keep_incomplete,
+            errors: errors.clone(),
+            written: 0,
+            skipped: 0,
+        })
+    }
+
+    pub fn write(&mut self, record: &Record, index: usize) -> Result<(), Box<dyn Error>> {
+        let row = self
+            .columns
+            .iter()
+            .map(|header| record.get(header).cloned().unwrap_or_default());
+
+        let missing = missing_headers(record, self.headers);
+        if missing.is_empty() || self.keep_incomplete {
+            self.wtr.write_row(row)?;
+            self.written += 1;
+            return Ok(());
+        }
+
+        let reason = format!("missing {}", missing.join(", "));
+        if self.strict {
+            // A value left blank by a failed step: report that failure rather than the blank.
+            let error = record
+                .position()
+                .and_then(|position| {
+                    self.errors.errors().into_iter().find(|error| {
+                        error.path.as_deref() == Some(&*position.path)
+                            && error.line == Some(position.line)
+                    })
+                })
+                .unwrap_or_else(|| RowError::new(record, index, &missing.join(", "), reason));
+            return Err(format!("strict: {}", error).into());
+        }
+        if let Some(rejects) = &mut self.rejects {
+            rejects.write_row(row.chain([reason]))?;
+        }
+        self.skipped += 1;
+        Ok(())
+    }
+
+    /// Flushes both files before moving either into place, so a failed flush leaves neither.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        let output = self.wtr.finish()?;
+        let rejects = self.rejects.map(RowSink::finish).transpose()?;
+        if let Some(rejects) = rejects {
+            rejects.commit()?;
+        }
+        output.commit()
+    }
+}